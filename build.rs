@@ -1,3 +1,12 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 fn main() {
     embuild::espidf::sysenv::output();
+    // Firmware build timestamp, seconds since the Unix epoch
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={timestamp}");
+    println!("cargo:rerun-if-changed=src");
 }
@@ -0,0 +1,16 @@
+//! Temperature reader and its Modbus and MQTT interfaces
+//!
+//! The reader and the register map build on the host as well, against the
+//! simulated bus, for the tests: `cargo test --lib --target
+//! x86_64-unknown-linux-gnu`. Everything touching ESP-IDF is left out there.
+
+#![cfg_attr(target_os = "espidf", feature(impl_trait_in_assoc_type))]
+// Their firmware callers are left out of host builds
+#![cfg_attr(not(target_os = "espidf"), allow(dead_code))]
+
+pub mod modbus;
+#[cfg(target_os = "espidf")]
+pub mod mqtt;
+#[cfg(target_os = "espidf")]
+pub mod relay;
+pub mod temperature;
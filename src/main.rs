@@ -1,9 +1,8 @@
-#![feature(impl_trait_in_assoc_type)]
-
 use anyhow::Result;
+use digital_thermometer_controller::{modbus, mqtt, relay::Relays, temperature};
 use esp_idf_svc::{
     eventloop::EspSystemEventLoop,
-    hal::{prelude::Peripherals, reset::restart},
+    hal::{gpio::OutputPin, prelude::Peripherals, reset::restart},
     io::vfs::MountedEventfs,
     log::EspLogger,
     nvs::EspDefaultNvsPartition,
@@ -12,10 +11,11 @@ use esp_idf_svc::{
     wifi::WifiEvent,
 };
 use log::{error, info, warn};
-use tokio::runtime::Builder;
+use std::time::Duration;
+use tokio::{runtime::Builder, spawn, sync::broadcast::error::RecvError};
 use wifi::connect;
 
-const _MAC_ADDRESS: &str = "7c:df:a1:a3:5a:f8";
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
 
 fn main() -> Result<()> {
     link_patches();
@@ -40,7 +40,20 @@ async fn run() -> Result<()> {
     let peripherals = Peripherals::take()?;
     let nvs = EspDefaultNvsPartition::take()?;
     // Initialize the network stack, this must be done before starting the server
-    let mut wifi = connect(peripherals.modem, event_loop.clone(), timer, Some(nvs)).await?;
+    let mut wifi = connect(
+        peripherals.modem,
+        event_loop.clone(),
+        timer,
+        Some(nvs.clone()),
+    )
+    .await?;
+    // Station MAC address, identifies the device
+    let mac_address = wifi
+        .sta_netif()
+        .get_mac()?
+        .map(|byte| format!("{byte:02x}"))
+        .join(":");
+    info!("MAC address: {mac_address}");
     let _subscription = event_loop.subscribe::<WifiEvent, _>(move |event| {
         info!("Got event: {event:?}");
         if let WifiEvent::StaDisconnected(_) = event {
@@ -52,13 +65,39 @@ async fn run() -> Result<()> {
     // Start deadline checker
     deadline::start();
     // Start temperature reader
-    let temperature_sender = temperature::start(peripherals.pins.gpio2, peripherals.rmt.channel0)?;
+    let temperature = temperature::start(
+        vec![
+            temperature::driver(peripherals.pins.gpio2, peripherals.rmt.channel0)?,
+            temperature::driver(peripherals.pins.gpio3, peripherals.rmt.channel1)?,
+        ],
+        nvs.clone(),
+        temperature::Options {
+            interval: SAMPLE_INTERVAL,
+            ..Default::default()
+        },
+    )?;
+    // Log sensor events
+    let mut events = temperature.subscribe();
+    spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => info!("Temperature event: {event:?}"),
+                Err(RecvError::Lagged(count)) => warn!("Temperature events lagged: {count}"),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+    // Relay outputs
+    let relays = Relays::new([
+        peripherals.pins.gpio4.downgrade_output(),
+        peripherals.pins.gpio5.downgrade_output(),
+    ])?;
+    // Start MQTT client
+    mqtt::start(temperature.clone(), mac_address.clone());
     // Run modbus server
-    modbus::run(temperature_sender.clone()).await?;
+    modbus::run(temperature, relays, nvs, mac_address).await?;
     Ok(())
 }
 
 mod deadline;
-mod modbus;
-mod temperature;
 mod wifi;
@@ -1,100 +1,759 @@
-use crate::temperature::Request as TemperatureRequest;
-use anyhow::Result;
-use log::{error, info};
-use std::{net::SocketAddr, sync::LazyLock};
-use tokio::{
-    net::TcpListener,
-    sync::{mpsc::Sender, oneshot},
-};
-use tokio_modbus::{
-    prelude::*,
-    server::{
-        Service,
-        tcp::{Server, accept_tcp_connection},
-    },
+//! Modbus TCP server
+//!
+//! Register map, multi-register values are big-endian words, floats are
+//! IEEE 754 single precision and timestamps seconds since the Unix epoch.
+//! Any window within an area can be read, requests reaching past its end
+//! are answered with `IllegalDataAddress` and empty ones with
+//! `IllegalDataValue`.
+//!
+//! Input registers:
+//!
+//! | Address          | Contents                                             |
+//! |------------------|------------------------------------------------------|
+//! | 0 + 6 × slot     | ROM (4), temperature (2)                             |
+//! | 1000 + 10 × slot | raw temperature, gain, offset, date, reference (2)   |
+//! | 2000 + 2 × slot  | filtered temperature (2)                             |
+//! | 3000 + 14 × slot | statistics since boot: start, count, min, max, mean, |
+//! |                  | min and max timestamps (2)                           |
+//! | 4000 + 14 × slot | statistics since the last reset, as above            |
+//! | 5000 + 3 × slot  | family code, power supply, bus                       |
+//! | 6000 + n × slot  | temperature in the selected encoding (n), the only   |
+//! |                  | area following the encoding                          |
+//! | 7000 + 4 × slot  | ROM (4)                                              |
+//! | 8000 + slot      | status                                               |
+//! | 8500 + 10 × slot | counters since boot of CRC failures, other failures, |
+//! |                  | power-on reset values, implausible jumps and retries |
+//! |                  | (2)                                                  |
+//! | 9000 + 14 × bus  | current fault, counters of no presence, short        |
+//! |                  | circuit, conflict, timeout and driver faults (2),    |
+//! |                  | last fault, its timestamp (2)                        |
+//! | 10000 + 5 × n    | history of the queried slot: timestamp (2),          |
+//! |                  | temperature (2), status, as many samples as match    |
+//! |                  | the query                                            |
+//!
+//! Discrete inputs, 4 × slot: high alarm, low alarm, fault and offline. An
+//! alarm is high or low by the side of the TH and TL triggers the raw
+//! temperature is on.
+//!
+//! Coils, writing a trigger off does nothing, a write outside the areas is
+//! refused with `IllegalDataAddress`:
+//!
+//! | Address         | Contents                                          |
+//! |-----------------|---------------------------------------------------|
+//! | 0 + slot        | alarm acknowledged, until it clears; write on to  |
+//! |                 | acknowledge                                       |
+//! | 1000 + slot     | trigger, reset statistics of the slot             |
+//! | 2000            | trigger, rescan the buses                         |
+//! | 3000 + relay    | relay output                                      |
+//!
+//! Holding registers, a write changing several slots is validated as a whole
+//! before any of them changes: refused with `IllegalDataAddress` if any slot
+//! is free and with `IllegalDataValue` if any value is invalid. A sensor
+//! failing to take its configuration fails the write, the slots before it
+//! keep their changes. Changes last until the next restart unless saved.
+//!
+//! | Address         | Contents                                          |
+//! |-----------------|---------------------------------------------------|
+//! | 0 + 2 × slot    | filter kind and parameter                         |
+//! | 1000            | history query: slot, earliest timestamp (2)       |
+//! | 1003            | read-only, samples matching the history query     |
+//! | 1100            | write-only, reset statistics of a slot or 0xFFFF  |
+//! | 1101            | write-only, copy scratchpad of a slot or 0xFFFF   |
+//! | 1102            | write-only, recall EEPROM of a slot or 0xFFFF     |
+//! | 1103            | write-only, save the settings in the NVS          |
+//! | 1104            | write-only, free the slot of an absent sensor, or |
+//! |                 | 0xFFFF for all absent sensors                     |
+//! | 1200            | sample interval, ms                               |
+//! | 1201            | encoding of the values area (6000) only, floats   |
+//! |                 | elsewhere stay ABCD                               |
+//! | 1202            | samples kept in the history of every sensor, at   |
+//! |                 | most 2400 shared by the sensors holding a slot    |
+//! | 2000 + 3 × slot | resolution in bits, TH and TL alarm triggers, °C  |
+//! | 3000 + 6 × slot | calibration gain (2), offset (2) and reference    |
+//! |                 | thermometer ID (2), dated when written            |
+//! | 4000 + 6 × slot | fault detection: largest rate of change, °C/min   |
+//! |                 | (2), flatline time, s (2), flatline band, °C (2), |
+//! |                 | zero turns a check off                            |
+//!
+//! Encodings of the values area, integers are signed, saturated and 0x8000
+//! without a temperature. Every other float register, input or holding, is
+//! always ABCD:
+//!
+//! | Value | Encoding                                     | Registers |
+//! |-------|----------------------------------------------|-----------|
+//! | 0     | float ABCD, big-endian words                 | 2         |
+//! | 1     | float CDAB, little-endian words              | 2         |
+//! | 2     | float BADC, bytes swapped in the words       | 2         |
+//! | 3     | float DCBA, little-endian                    | 2         |
+//! | 4     | integer, 0.1 °C                              | 1         |
+//! | 5     | integer, 0.01 °C                             | 1         |
+//! | 6     | integer, 1/16 °C raw counts, uncalibrated    | 1         |
+//!
+//! Read Device Identification (43 / 14), basic, regular and extended
+//! objects:
+//!
+//! | Object | Contents                                  |
+//! |--------|-------------------------------------------|
+//! | 0x00   | vendor name                               |
+//! | 0x01   | product code                              |
+//! | 0x02   | version                                   |
+//! | 0x04   | product name                              |
+//! | 0x05   | model name                                |
+//! | 0x80   | MAC address                               |
+//! | 0x81   | number of sensors present                 |
+//! | 0x82   | firmware build timestamp                  |
+
+#[cfg(target_os = "espidf")]
+pub use self::server::run;
+
+use crate::temperature::{
+    Calibration, Configuration, Filter, Handle as Temperature, Health, Limits, Reading, Sample,
+    Statistics, Status,
 };
+use anyhow::Result;
+use log::error;
+use std::{fmt::Debug, ops::Range, sync::Mutex, time::Duration};
+use tokio_modbus::prelude::*;
 
-const INPUT_REGISTER_SIZE: usize = 6;
+/// Input register areas: offset, registers per slot and their contents
+const INPUT_REGISTERS: [(u16, usize, Registers); 9] = [
+    (0, 6, reading_registers),
+    (1000, 10, calibration_registers),
+    (2000, 2, filtered_registers),
+    (3000, 14, statistics_registers),
+    (4000, 14, window_registers),
+    (5000, 3, device_registers),
+    (ROM_OFFSET, 4, rom_registers),
+    (8000, 1, status_registers),
+    (8500, 10, counter_registers),
+];
+/// Temperatures only, in the selected encoding, its size per slot follows the
+/// encoding
+const VALUES_OFFSET: u16 = 6000;
+const ROM_OFFSET: u16 = 7000;
+/// Health of every bus: current fault, fault counters, last fault and its
+/// timestamp
+const HEALTH_OFFSET: u16 = 9000;
+const HEALTH_REGISTER_SIZE: usize = 14;
+/// History samples of the queried slot: timestamp, temperature and status.
+/// Last, its size follows the number of samples matching the query.
+const HISTORY_OFFSET: u16 = 10000;
+const SAMPLE_REGISTER_SIZE: usize = 5;
+/// Holding register areas: offset, registers per slot and their contents.
+/// Changes last until the next restart unless saved.
+const HOLDING_REGISTERS: [(u16, usize, Registers); 4] = [
+    (0, 2, filter_registers),
+    (CONFIGURATION_OFFSET, 3, configuration_registers),
+    (CALIBRATION_OFFSET, 6, calibration_holding_registers),
+    (LIMITS_OFFSET, 6, limits_registers),
+];
+const CONFIGURATION_OFFSET: u16 = 2000;
+const CALIBRATION_OFFSET: u16 = 3000;
+const LIMITS_OFFSET: u16 = 4000;
+/// History query of the connection: slot and earliest timestamp
+const QUERY_OFFSET: u16 = 1000;
+const QUERY_REGISTER_SIZE: usize = 3;
+/// Number of samples matching the history query, read-only
+const SAMPLES_REGISTER: u16 = 1003;
+/// Write-only command registers, written with a slot or `ALL_SLOTS`
+const COMMAND_REGISTERS: Range<u16> = 1100..1105;
+/// Starts the statistics window over
+const RESET_STATISTICS: u16 = 1100;
+/// Stores the configuration in the sensor EEPROM, unless it already holds it
+const COPY_SCRATCHPAD: u16 = 1101;
+/// Reloads the configuration from the sensor EEPROM
+const RECALL_EEPROM: u16 = 1102;
+/// Saves the settings in the NVS, the slot is ignored
+const SAVE_SETTINGS: u16 = 1103;
+/// Frees the slot of an absent sensor
+const RELEASE_SLOT: u16 = 1104;
+const ALL_SLOTS: u16 = 0xFFFF;
+/// Sample interval, milliseconds
+const INTERVAL_REGISTER: u16 = 1200;
+/// Encoding of the values area, the other float registers are always ABCD
+const ENCODING_REGISTER: u16 = 1201;
+/// Samples kept in the history of every sensor
+const HISTORY_REGISTER: u16 = 1202;
 
-static SOCKET_ADDR: LazyLock<SocketAddr> = LazyLock::new(|| "0.0.0.0:5502".parse().unwrap());
+/// Discrete inputs per slot: high alarm, low alarm, fault and offline
+const INPUT_SIZE: usize = 4;
+/// Coil areas: offset and coils of a slot, one per slot
+const COILS: [(u16, Coils); 2] = [(0, acknowledged_coils), (STATISTICS_COILS, |_| vec![false])];
+/// Reset statistics of a slot, reads off
+const STATISTICS_COILS: u16 = 1000;
+/// Rescan trigger, reads off
+const RESCAN_COIL: u16 = 2000;
+/// Relay outputs, one per relay
+const RELAY_OFFSET: u16 = 3000;
 
-pub(super) async fn run(temperature_sender: Sender<TemperatureRequest>) -> Result<()> {
-    let server = Server::new(TcpListener::bind(*SOCKET_ADDR).await?);
-    let new_service = |_socket_addr| Ok(Some(ExampleService::new(temperature_sender.clone())));
-    let on_connected = |stream, socket_addr| async move {
-        accept_tcp_connection(stream, socket_addr, new_service)
-    };
-    let on_process_error = |error| error!("{error}");
-    server.serve(&on_connected, on_process_error).await?;
-    Ok(())
+/// Registers of a slot
+type Registers = fn(&Reading) -> Vec<u16>;
+/// Coils of a slot
+type Coils = fn(&Reading) -> Vec<bool>;
+
+/// History query, a client backfills by moving `from` past the latest sample
+/// it received
+#[derive(Clone, Copy, Debug, Default)]
+struct Query {
+    slot: u16,
+    /// Seconds since the Unix epoch
+    from: u32,
+}
+
+impl Query {
+    fn registers(&self) -> [u16; QUERY_REGISTER_SIZE] {
+        let from = self.from.to_be_bytes();
+        [
+            self.slot,
+            u16::from_be_bytes([from[0], from[1]]),
+            u16::from_be_bytes([from[2], from[3]]),
+        ]
+    }
+
+    fn from_registers(registers: [u16; QUERY_REGISTER_SIZE]) -> Self {
+        Self {
+            slot: registers[0],
+            from: (registers[1] as u32) << 16 | registers[2] as u32,
+        }
+    }
 }
 
-struct ExampleService {
-    temperature_sender: Sender<TemperatureRequest>,
+/// Reads a window of the per-slot blocks of `size` registers
+fn read<T>(
+    temperature: &Temperature,
+    address: u16,
+    count: u16,
+    size: usize,
+    registers: impl Fn(&Reading) -> Vec<T>,
+) -> Result<Vec<T>, ExceptionCode> {
+    window(
+        temperature.readings().as_slice(),
+        address,
+        count,
+        size,
+        registers,
+    )
 }
 
-impl ExampleService {
-    fn new(temperature_sender: Sender<TemperatureRequest>) -> Self {
-        Self { temperature_sender }
+/// Reads a window of the per-bus blocks
+fn read_health(
+    temperature: &Temperature,
+    address: u16,
+    count: u16,
+) -> Result<Vec<u16>, ExceptionCode> {
+    window(
+        &temperature.health(),
+        address,
+        count,
+        HEALTH_REGISTER_SIZE,
+        health_registers,
+    )
+}
+
+/// Reads a window of the history of the queried slot
+async fn read_history(
+    temperature: &Temperature,
+    query: Query,
+    address: u16,
+    count: u16,
+) -> Result<Vec<u16>, ExceptionCode> {
+    window(
+        &history(temperature, query).await?,
+        address,
+        count,
+        SAMPLE_REGISTER_SIZE,
+        |sample| sample_registers(sample).to_vec(),
+    )
+}
+
+/// Samples matching the history query
+async fn history(temperature: &Temperature, query: Query) -> Result<Vec<Sample>, ExceptionCode> {
+    temperature
+        .history(query.slot as _, query.from..u32::MAX)
+        .await
+        .map_err(exception)
+}
+
+/// Window of `count` registers at `address` over consecutive blocks of
+/// `size` registers each
+fn window<B, T>(
+    blocks: &[B],
+    address: u16,
+    count: u16,
+    size: usize,
+    registers: impl Fn(&B) -> Vec<T>,
+) -> Result<Vec<T>, ExceptionCode> {
+    let touched = self::blocks(address, count, size, blocks.len())?;
+    Ok(blocks[touched]
+        .iter()
+        .flat_map(registers)
+        .skip(address as usize % size)
+        .take(count as _)
+        .collect())
+}
+
+/// Blocks the window of `count` registers at `address` touches, out of `len`
+/// blocks of `size` registers
+fn blocks(
+    address: u16,
+    count: u16,
+    size: usize,
+    len: usize,
+) -> Result<Range<usize>, ExceptionCode> {
+    if count == 0 {
+        error!("IllegalDataValue {{ address: {address}, count: {count} }}");
+        return Err(ExceptionCode::IllegalDataValue);
+    }
+    let (start, end) = (address as usize, address as usize + count as usize);
+    if end > size * len {
+        error!("IllegalDataAddress {{ address: {address}, count: {count}, len: {len} }}");
+        return Err(ExceptionCode::IllegalDataAddress);
     }
+    Ok(start / size..end.div_ceil(size))
+}
+
+fn write_query(query: &Mutex<Query>, address: u16, values: &[u16]) -> Result<(), ExceptionCode> {
+    let mut query = query.lock().unwrap();
+    let mut registers = query.registers();
+    let start = address as usize;
+    registers
+        .get_mut(start..start + values.len())
+        .ok_or(ExceptionCode::IllegalDataAddress)?
+        .copy_from_slice(values);
+    *query = Query::from_registers(registers);
+    Ok(())
 }
 
-impl Service for ExampleService {
-    type Request = Request<'static>;
-    type Response = Response;
-    type Exception = ExceptionCode;
-    type Future = impl Future<Output = Result<Self::Response, Self::Exception>>;
-
-    fn call(&self, request: Self::Request) -> Self::Future {
-        info!("Modbus request: {request:?}");
-        let temperature_sender = self.temperature_sender.clone();
-        async move {
-            match request {
-                Request::ReadInputRegisters(address, count) => {
-                    let address = address as usize;
-                    let count = count as usize;
-                    if address % INPUT_REGISTER_SIZE != 0 || count % INPUT_REGISTER_SIZE != 0 {
-                        error!("IllegalAddress {{ address: {address}, count: {count} }}");
-                        return Err(ExceptionCode::IllegalDataAddress);
-                    }
-                    let start = address / INPUT_REGISTER_SIZE;
-                    let end = start + count / INPUT_REGISTER_SIZE;
-                    let (sender, receiver) = oneshot::channel();
-                    if let Err(error) = temperature_sender.send((start..end, sender)).await {
-                        error!("{error:?}");
-                        return Err(ExceptionCode::ServerDeviceFailure);
-                    };
-                    let input_registers: Vec<_> = match receiver.await {
-                        Ok(Ok(temperatures)) => temperatures
-                            .into_iter()
-                            .flat_map(|(address, temperature)| {
-                                let address = address.to_be_bytes();
-                                let temperature = temperature.to_be_bytes();
-                                [
-                                    u16::from_be_bytes([address[0], address[1]]),
-                                    u16::from_be_bytes([address[2], address[3]]),
-                                    u16::from_be_bytes([address[4], address[5]]),
-                                    u16::from_be_bytes([address[6], address[7]]),
-                                    u16::from_be_bytes([temperature[0], temperature[1]]),
-                                    u16::from_be_bytes([temperature[2], temperature[3]]),
-                                ]
-                            })
-                            .collect(),
-                        Ok(Err(error)) => {
-                            error!("{error:?}");
-                            return Err(error.into());
-                        }
-                        Err(error) => {
-                            error!("{error:?}");
-                            return Err(ExceptionCode::ServerDeviceFailure);
-                        }
-                    };
-                    Ok(Response::ReadInputRegisters(
-                        input_registers[address..count].to_vec(),
-                    ))
-                }
-                _ => Err(ExceptionCode::IllegalFunction),
+async fn set_interval(temperature: &Temperature, value: u16) -> Result<(), ExceptionCode> {
+    temperature
+        .set_interval(Duration::from_millis(value as _))
+        .await
+        .map_err(exception)
+}
+
+async fn set_history_length(temperature: &Temperature, value: u16) -> Result<(), ExceptionCode> {
+    temperature
+        .set_history_length(value as _)
+        .await
+        .map_err(exception)
+}
+
+/// Writes a window of a per-slot holding register area
+async fn write(
+    temperature: &Temperature,
+    address: u16,
+    values: &[u16],
+) -> Result<(), ExceptionCode> {
+    match address {
+        LIMITS_OFFSET.. => {
+            let limits = parse(
+                temperature,
+                address - LIMITS_OFFSET,
+                values,
+                limits_registers,
+                |&[
+                    rate_high,
+                    rate_low,
+                    flatline_high,
+                    flatline_low,
+                    band_high,
+                    band_low,
+                ]| {
+                    Limits::new(
+                        float(rate_high, rate_low),
+                        Duration::from_secs(
+                            ((flatline_high as u32) << 16 | flatline_low as u32) as _,
+                        ),
+                        float(band_high, band_low),
+                    )
+                },
+            )?;
+            for (slot, limits) in limits {
+                temperature
+                    .set_limits(slot, limits)
+                    .await
+                    .map_err(exception)?;
             }
         }
+        CALIBRATION_OFFSET.. => {
+            let calibrations = parse(
+                temperature,
+                address - CALIBRATION_OFFSET,
+                values,
+                calibration_holding_registers,
+                |&[
+                    gain_high,
+                    gain_low,
+                    offset_high,
+                    offset_low,
+                    reference_high,
+                    reference_low,
+                ]| {
+                    Calibration::new(
+                        float(gain_high, gain_low),
+                        float(offset_high, offset_low),
+                        (reference_high as u32) << 16 | reference_low as u32,
+                    )
+                },
+            )?;
+            for (slot, calibration) in calibrations {
+                temperature
+                    .calibrate(slot, calibration)
+                    .await
+                    .map_err(exception)?;
+            }
+        }
+        CONFIGURATION_OFFSET.. => {
+            let configurations = parse(
+                temperature,
+                address - CONFIGURATION_OFFSET,
+                values,
+                configuration_registers,
+                |&[bits, high, low]| {
+                    Configuration::new(
+                        bits.try_into().ok()?,
+                        (high as i16).try_into().ok()?,
+                        (low as i16).try_into().ok()?,
+                    )
+                },
+            )?;
+            for (slot, configuration) in configurations {
+                temperature
+                    .configure(slot, configuration)
+                    .await
+                    .map_err(exception)?;
+            }
+        }
+        _ => {
+            let filters = parse(
+                temperature,
+                address,
+                values,
+                filter_registers,
+                |&[kind, parameter]| Filter::new(kind, parameter),
+            )?;
+            for (slot, filter) in filters {
+                temperature
+                    .set_filter(slot, filter)
+                    .await
+                    .map_err(exception)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses the blocks of `SIZE` registers a write touches, a partially
+/// written block keeps the rest of its current registers
+///
+/// Every block is validated before any slot changes, free slots cannot be
+/// written.
+fn parse<const SIZE: usize, T>(
+    temperature: &Temperature,
+    address: u16,
+    values: &[u16],
+    registers: Registers,
+    from_registers: impl Fn(&[u16; SIZE]) -> Option<T>,
+) -> Result<Vec<(usize, T)>, ExceptionCode> {
+    let readings = temperature.readings();
+    let blocks = blocks(address, values.len() as _, SIZE, readings.as_slice().len())?;
+    let start = blocks.start;
+    let readings = &readings.as_slice()[blocks];
+    if let Some(free) = readings.iter().position(|reading| reading.address == 0) {
+        error!("FreeSlot {{ slot: {} }}", start + free);
+        return Err(ExceptionCode::IllegalDataAddress);
+    }
+    let mut registers: Vec<_> = readings.iter().flat_map(registers).collect();
+    registers[address as usize % SIZE..][..values.len()].copy_from_slice(values);
+    registers
+        .as_chunks::<SIZE>()
+        .0
+        .iter()
+        .map(from_registers)
+        .collect::<Option<Vec<_>>>()
+        .map(|values| (start..).zip(values).collect())
+        .ok_or(ExceptionCode::IllegalDataValue)
+}
+
+/// ROM address and calibrated temperature
+fn reading_registers(reading: &Reading) -> Vec<u16> {
+    let mut registers = words(&reading.address.to_be_bytes());
+    registers.extend(words(&reading.temperature.to_be_bytes()));
+    registers
+}
+
+fn status_registers(reading: &Reading) -> Vec<u16> {
+    vec![reading.status as _]
+}
+
+/// Failure counters of the sensor since boot
+fn counter_registers(reading: &Reading) -> Vec<u16> {
+    let counters = &reading.counters;
+    [
+        counters.crc,
+        counters.failed,
+        counters.power_on_reset,
+        counters.implausible,
+        counters.retries,
+    ]
+    .iter()
+    .flat_map(|counter| words(&counter.to_be_bytes()))
+    .collect()
+}
+
+fn rom_registers(reading: &Reading) -> Vec<u16> {
+    words(&reading.address.to_be_bytes())
+}
+
+/// Raw temperature and the calibration applied to it
+fn calibration_registers(reading: &Reading) -> Vec<u16> {
+    let calibration = &reading.calibration;
+    [
+        reading.raw.to_be_bytes(),
+        calibration.gain.to_be_bytes(),
+        calibration.offset.to_be_bytes(),
+        calibration.date.to_be_bytes(),
+        calibration.reference.to_be_bytes(),
+    ]
+    .iter()
+    .flat_map(|bytes| words(bytes))
+    .collect()
+}
+
+fn sample_registers(sample: &Sample) -> [u16; SAMPLE_REGISTER_SIZE] {
+    let timestamp = sample.timestamp.to_be_bytes();
+    let temperature = sample.temperature.to_be_bytes();
+    [
+        u16::from_be_bytes([timestamp[0], timestamp[1]]),
+        u16::from_be_bytes([timestamp[2], timestamp[3]]),
+        u16::from_be_bytes([temperature[0], temperature[1]]),
+        u16::from_be_bytes([temperature[2], temperature[3]]),
+        sample.status as _,
+    ]
+}
+
+/// Filtered temperature
+fn filtered_registers(reading: &Reading) -> Vec<u16> {
+    words(&reading.filtered.to_be_bytes())
+}
+
+fn filter_registers(reading: &Reading) -> Vec<u16> {
+    reading.filter.registers().to_vec()
+}
+
+/// Resolution in bits and the TH and TL alarm triggers, °C
+fn configuration_registers(reading: &Reading) -> Vec<u16> {
+    let configuration = &reading.configuration;
+    vec![
+        configuration.bits() as _,
+        configuration.alarm_high_trigger as i16 as _,
+        configuration.alarm_low_trigger as i16 as _,
+    ]
+}
+
+/// Calibration gain, offset and reference thermometer ID
+fn calibration_holding_registers(reading: &Reading) -> Vec<u16> {
+    let calibration = &reading.calibration;
+    [
+        calibration.gain.to_be_bytes(),
+        calibration.offset.to_be_bytes(),
+        calibration.reference.to_be_bytes(),
+    ]
+    .iter()
+    .flat_map(|bytes| words(bytes))
+    .collect()
+}
+
+/// Largest rate of change, flatline time in seconds and flatline band
+fn limits_registers(reading: &Reading) -> Vec<u16> {
+    let limits = &reading.limits;
+    [
+        limits.rate.to_be_bytes(),
+        (limits.flatline.as_secs() as u32).to_be_bytes(),
+        limits.band.to_be_bytes(),
+    ]
+    .iter()
+    .flat_map(|bytes| words(bytes))
+    .collect()
+}
+
+/// Statistics since boot
+fn statistics_registers(reading: &Reading) -> Vec<u16> {
+    statistics(&reading.statistics)
+}
+
+/// Statistics since the last reset
+fn window_registers(reading: &Reading) -> Vec<u16> {
+    statistics(&reading.window)
+}
+
+/// Start, count, minimum, maximum, mean and the times of the extremes
+fn statistics(statistics: &Statistics) -> Vec<u16> {
+    [
+        statistics.start.to_be_bytes(),
+        statistics.count.to_be_bytes(),
+        statistics.min.to_be_bytes(),
+        statistics.max.to_be_bytes(),
+        statistics.mean.to_be_bytes(),
+        statistics.min_timestamp.to_be_bytes(),
+        statistics.max_timestamp.to_be_bytes(),
+    ]
+    .iter()
+    .flat_map(|bytes| words(bytes))
+    .collect()
+}
+
+/// High and low alarms, by the side of the triggers the raw temperature is
+/// on, fault and offline
+fn alarm_inputs(reading: &Reading) -> Vec<bool> {
+    let configuration = &reading.configuration;
+    let middle =
+        (configuration.alarm_high_trigger as f32 + configuration.alarm_low_trigger as f32) / 2.0;
+    vec![
+        reading.alarm && reading.raw >= middle,
+        reading.alarm && reading.raw < middle,
+        matches!(
+            reading.status,
+            Status::Crc
+                | Status::PowerOnReset
+                | Status::Implausible
+                | Status::RateOfChange
+                | Status::Flatline
+                | Status::DeviceFault
+        ),
+        matches!(reading.status, Status::NotPresent | Status::Stale),
+    ]
+}
+
+/// The alarm was acknowledged
+fn acknowledged_coils(reading: &Reading) -> Vec<bool> {
+    vec![reading.acknowledged]
+}
+
+/// Family code, power supply and bus
+fn device_registers(reading: &Reading) -> Vec<u16> {
+    vec![
+        reading.address as u8 as _,
+        reading.power as _,
+        reading.bus as _,
+    ]
+}
+
+/// Current fault (zero for none), fault counters, last fault and its
+/// timestamp
+fn health_registers(health: &Health) -> Vec<u16> {
+    let faults = &health.faults;
+    let (last, timestamp) = health
+        .last
+        .map_or((0, 0), |(fault, timestamp)| (fault as _, timestamp));
+    let mut registers = vec![health.current.map_or(0, |fault| fault as _)];
+    registers.extend(
+        [
+            faults.no_presence,
+            faults.short_circuit,
+            faults.conflict,
+            faults.timeout,
+            faults.driver,
+        ]
+        .iter()
+        .flat_map(|counter| words(&counter.to_be_bytes())),
+    );
+    registers.push(last);
+    registers.extend(words(&timestamp.to_be_bytes()));
+    registers
+}
+
+fn is_query(address: u16) -> bool {
+    (QUERY_OFFSET..QUERY_OFFSET + QUERY_REGISTER_SIZE as u16).contains(&address)
+}
+
+/// Logs the error and maps it to its exception
+fn exception<E: Debug + Into<ExceptionCode>>(error: E) -> ExceptionCode {
+    error!("{error:?}");
+    error.into()
+}
+
+/// Float from its big-endian words
+fn float(high: u16, low: u16) -> f32 {
+    f32::from_bits((high as u32) << 16 | low as u32)
+}
+
+fn words(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]))
+        .collect()
+}
+
+mod encoding;
+mod identification;
+#[cfg(target_os = "espidf")]
+mod server;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Blocks of three registers, numbered by block and register
+    fn registers(block: &u16) -> Vec<u16> {
+        (0..3).map(|register| 10 * block + register).collect()
+    }
+
+    #[test]
+    fn aligned() {
+        assert_eq!(blocks(0, 3, 3, 4), Ok(0..1));
+        assert_eq!(
+            window(&[0, 1, 2, 3], 3, 6, 3, registers),
+            Ok(vec![10, 11, 12, 20, 21, 22])
+        );
+    }
+
+    #[test]
+    fn unaligned() {
+        assert_eq!(blocks(1, 1, 3, 4), Ok(0..1));
+        assert_eq!(window(&[0, 1, 2, 3], 1, 1, 3, registers), Ok(vec![1]));
+        assert_eq!(window(&[0, 1, 2, 3], 4, 2, 3, registers), Ok(vec![11, 12]));
+    }
+
+    #[test]
+    fn crossing_blocks() {
+        assert_eq!(blocks(2, 2, 3, 4), Ok(0..2));
+        assert_eq!(window(&[0, 1, 2, 3], 2, 2, 3, registers), Ok(vec![2, 10]));
+        assert_eq!(blocks(1, 7, 3, 4), Ok(0..3));
+        assert_eq!(
+            window(&[0, 1, 2, 3], 1, 7, 3, registers),
+            Ok(vec![1, 2, 10, 11, 12, 20, 21])
+        );
+    }
+
+    #[test]
+    fn last_block() {
+        assert_eq!(blocks(9, 3, 3, 4), Ok(3..4));
+        assert_eq!(window(&[0, 1, 2, 3], 11, 1, 3, registers), Ok(vec![32]));
+        assert_eq!(
+            window(&[0, 1, 2, 3], 0, 12, 3, registers).map(|registers| registers.len()),
+            Ok(12)
+        );
+    }
+
+    #[test]
+    fn past_the_end() {
+        assert_eq!(blocks(12, 1, 3, 4), Err(ExceptionCode::IllegalDataAddress));
+        assert_eq!(blocks(11, 2, 3, 4), Err(ExceptionCode::IllegalDataAddress));
+        assert_eq!(blocks(0, 13, 3, 4), Err(ExceptionCode::IllegalDataAddress));
+        assert_eq!(blocks(0, 1, 3, 0), Err(ExceptionCode::IllegalDataAddress));
+        assert_eq!(
+            blocks(u16::MAX, u16::MAX, 3, 4),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(blocks(0, 0, 3, 4), Err(ExceptionCode::IllegalDataValue));
+        assert_eq!(
+            window(&[0, 1, 2, 3], 3, 0, 3, registers),
+            Err(ExceptionCode::IllegalDataValue)
+        );
     }
 }
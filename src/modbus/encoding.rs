@@ -0,0 +1,113 @@
+use crate::temperature::Reading;
+
+/// Temperature encoding of the values area
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(u16)]
+pub(super) enum Encoding {
+    /// IEEE 754 single precision, big-endian words of big-endian bytes
+    #[default]
+    Abcd = 0,
+    /// Little-endian words of big-endian bytes
+    Cdab = 1,
+    /// Big-endian words of little-endian bytes
+    Badc = 2,
+    /// Little-endian words of little-endian bytes
+    Dcba = 3,
+    /// Signed, 0.1 °C
+    Tenths = 4,
+    /// Signed, 0.01 °C
+    Hundredths = 5,
+    /// Signed counts of 1/16 °C, the uncalibrated sensor temperature
+    Raw = 6,
+}
+
+impl Encoding {
+    pub(super) fn new(value: u16) -> Option<Self> {
+        match value {
+            0 => Some(Self::Abcd),
+            1 => Some(Self::Cdab),
+            2 => Some(Self::Badc),
+            3 => Some(Self::Dcba),
+            4 => Some(Self::Tenths),
+            5 => Some(Self::Hundredths),
+            6 => Some(Self::Raw),
+            _ => None,
+        }
+    }
+
+    /// Registers per value
+    pub(super) fn size(self) -> usize {
+        match self {
+            Self::Abcd | Self::Cdab | Self::Badc | Self::Dcba => 2,
+            Self::Tenths | Self::Hundredths | Self::Raw => 1,
+        }
+    }
+
+    /// Temperature of the reading
+    pub(super) fn registers(self, reading: &Reading) -> Vec<u16> {
+        self.encode(reading.temperature, reading.raw)
+    }
+
+    /// Calibrated temperature, or the raw one for raw counts
+    fn encode(self, temperature: f32, raw: f32) -> Vec<u16> {
+        let [a, b, c, d] = temperature.to_be_bytes();
+        let words = |bytes: [[u8; 2]; 2]| bytes.map(u16::from_be_bytes).to_vec();
+        match self {
+            Self::Abcd => words([[a, b], [c, d]]),
+            Self::Cdab => words([[c, d], [a, b]]),
+            Self::Badc => words([[b, a], [d, c]]),
+            Self::Dcba => words([[d, c], [b, a]]),
+            Self::Tenths => vec![scaled(temperature, 10.0)],
+            Self::Hundredths => vec![scaled(temperature, 100.0)],
+            Self::Raw => vec![scaled(raw, 16.0)],
+        }
+    }
+}
+
+/// Rounded, saturating at the `i16` range, `i16::MIN` for NaN
+fn scaled(value: f32, scale: f32) -> u16 {
+    if value.is_nan() {
+        return i16::MIN as _;
+    }
+    (value * scale).round() as i16 as _
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn floats() {
+        // 21.5 is 0x41AC0000
+        for (encoding, registers) in [
+            (Encoding::Abcd, [0x41AC, 0x0000]),
+            (Encoding::Cdab, [0x0000, 0x41AC]),
+            (Encoding::Badc, [0xAC41, 0x0000]),
+            (Encoding::Dcba, [0x0000, 0xAC41]),
+        ] {
+            assert_eq!(encoding.encode(21.5, 21.0), registers);
+            assert_eq!(encoding.size(), 2);
+        }
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(Encoding::Tenths.encode(-21.56, 0.0), [-216i16 as u16]);
+        assert_eq!(Encoding::Hundredths.encode(21.555, 0.0), [2156]);
+        // Raw counts of the uncalibrated temperature
+        assert_eq!(Encoding::Raw.encode(21.5, 21.0625), [337]);
+        // Saturated
+        assert_eq!(Encoding::Hundredths.encode(400.0, 0.0), [i16::MAX as u16]);
+        assert_eq!(Encoding::Tenths.encode(-4000.0, 0.0), [i16::MIN as u16]);
+        // No temperature
+        assert_eq!(Encoding::Tenths.encode(f32::NAN, 0.0), [0x8000]);
+        assert_eq!(Encoding::Raw.size(), 1);
+    }
+
+    #[test]
+    fn new() {
+        assert_eq!(Encoding::new(Encoding::Dcba as _), Some(Encoding::Dcba));
+        assert_eq!(Encoding::new(6), Some(Encoding::Raw));
+        assert_eq!(Encoding::new(7), None);
+    }
+}
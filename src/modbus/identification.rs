@@ -0,0 +1,187 @@
+use crate::temperature::{Handle as Temperature, Status};
+use tokio_modbus::prelude::ExceptionCode;
+
+/// Encapsulated interface transport
+pub(super) const FUNCTION: u8 = 0x2B;
+/// Read Device Identification interface
+const MEI_TYPE: u8 = 0x0E;
+/// Extended identification, stream and individual access
+const CONFORMITY_LEVEL: u8 = 0x83;
+/// Response data after the function code
+const MAX_LENGTH: usize = 252;
+
+const VENDOR_NAME: &str = "IPPRAS";
+const PRODUCT_CODE: &str = env!("CARGO_PKG_NAME");
+const REVISION: &str = env!("CARGO_PKG_VERSION");
+const PRODUCT_NAME: &str = "Digital thermometer controller";
+const MODEL_NAME: &str = "ESP32-C3";
+/// Seconds since the Unix epoch
+const BUILD_TIMESTAMP: &str = env!("BUILD_TIMESTAMP");
+
+/// Last object of the basic and regular categories, extended objects follow
+const BASIC: u8 = 0x02;
+const REGULAR: u8 = 0x06;
+
+/// Read device ID codes
+const STREAM_BASIC: u8 = 1;
+const STREAM_REGULAR: u8 = 2;
+const STREAM_EXTENDED: u8 = 3;
+const INDIVIDUAL: u8 = 4;
+
+/// Responds to a Read Device Identification request: MEI type, read device ID
+/// code and object ID
+pub(super) fn respond(
+    temperature: &Temperature,
+    mac_address: &str,
+    request: &[u8],
+) -> Result<Vec<u8>, ExceptionCode> {
+    let &[mei_type, code, id] = request else {
+        return Err(ExceptionCode::IllegalDataValue);
+    };
+    if mei_type != MEI_TYPE {
+        return Err(ExceptionCode::IllegalFunction);
+    }
+    stream(code, id, objects(temperature, mac_address))
+}
+
+/// Response data carrying the objects the read device ID code selects
+///
+/// A stream starting at an unknown object starts over at the first one, a
+/// stream not fitting a response is continued by a request for the next
+/// object.
+fn stream(code: u8, id: u8, objects: Vec<(u8, String)>) -> Result<Vec<u8>, ExceptionCode> {
+    let objects = match code {
+        STREAM_BASIC | STREAM_REGULAR | STREAM_EXTENDED => {
+            let last = match code {
+                STREAM_BASIC => BASIC,
+                STREAM_REGULAR => REGULAR,
+                _ => u8::MAX,
+            };
+            let objects: Vec<_> = objects
+                .into_iter()
+                .filter(|&(object, _)| object <= last)
+                .collect();
+            let start = objects
+                .iter()
+                .position(|&(object, _)| object == id)
+                .unwrap_or_default();
+            objects[start..].to_vec()
+        }
+        INDIVIDUAL => {
+            let object = objects
+                .into_iter()
+                .find(|&(object, _)| object == id)
+                .ok_or(ExceptionCode::IllegalDataAddress)?;
+            vec![object]
+        }
+        _ => return Err(ExceptionCode::IllegalDataValue),
+    };
+    // MEI type, code, conformity level, more follows, next object and count
+    let mut response = vec![MEI_TYPE, code, CONFORMITY_LEVEL, 0, 0, 0];
+    for (object, value) in objects {
+        if response.len() + 2 + value.len() > MAX_LENGTH {
+            response[3] = 0xFF;
+            response[4] = object;
+            break;
+        }
+        response.extend([object, value.len() as _]);
+        response.extend(value.as_bytes());
+        response[5] += 1;
+    }
+    Ok(response)
+}
+
+/// Object IDs and values, in ID order
+fn objects(temperature: &Temperature, mac_address: &str) -> Vec<(u8, String)> {
+    let sensors = temperature
+        .readings()
+        .iter()
+        .filter(|reading| reading.address != 0 && reading.status != Status::NotPresent)
+        .count();
+    vec![
+        (0x00, VENDOR_NAME.to_owned()),
+        (0x01, PRODUCT_CODE.to_owned()),
+        (0x02, REVISION.to_owned()),
+        (0x04, PRODUCT_NAME.to_owned()),
+        (0x05, MODEL_NAME.to_owned()),
+        (0x80, mac_address.to_owned()),
+        (0x81, sensors.to_string()),
+        (0x82, BUILD_TIMESTAMP.to_owned()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn objects() -> Vec<(u8, String)> {
+        vec![
+            (0x00, "A".to_owned()),
+            (0x01, "BC".to_owned()),
+            (0x02, "D".to_owned()),
+            (0x04, "E".to_owned()),
+            (0x80, "F".to_owned()),
+        ]
+    }
+
+    #[test]
+    fn basic() {
+        assert_eq!(
+            stream(STREAM_BASIC, 0x00, objects()).unwrap(),
+            [
+                MEI_TYPE,
+                STREAM_BASIC,
+                CONFORMITY_LEVEL,
+                0,
+                0,
+                3,
+                0x00,
+                1,
+                b'A',
+                0x01,
+                2,
+                b'B',
+                b'C',
+                0x02,
+                1,
+                b'D'
+            ]
+        );
+    }
+
+    #[test]
+    fn extended() {
+        // From the requested object on
+        let response = stream(STREAM_EXTENDED, 0x04, objects()).unwrap();
+        assert_eq!(response[3..], [0, 0, 2, 0x04, 1, b'E', 0x80, 1, b'F']);
+        // An unknown object starts over
+        let response = stream(STREAM_REGULAR, 0x03, objects()).unwrap();
+        assert_eq!(response[5], 4);
+        assert_eq!(response[6], 0x00);
+    }
+
+    #[test]
+    fn individual() {
+        let response = stream(INDIVIDUAL, 0x80, objects()).unwrap();
+        assert_eq!(response[3..], [0, 0, 1, 0x80, 1, b'F']);
+        assert_eq!(
+            stream(INDIVIDUAL, 0x03, objects()),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+        assert_eq!(
+            stream(5, 0x00, objects()),
+            Err(ExceptionCode::IllegalDataValue)
+        );
+    }
+
+    #[test]
+    fn more_follows() {
+        let objects = vec![(0x80, "x".repeat(200)), (0x81, "y".repeat(100))];
+        let response = stream(STREAM_EXTENDED, 0x80, objects.clone()).unwrap();
+        assert!(response.len() <= MAX_LENGTH);
+        // More follows, continued at the next object
+        assert_eq!(response[3..6], [0xFF, 0x81, 1]);
+        let response = stream(STREAM_EXTENDED, 0x81, objects).unwrap();
+        assert_eq!(response[3..6], [0, 0, 1]);
+    }
+}
@@ -0,0 +1,365 @@
+use super::{
+    ALL_SLOTS, COILS, COMMAND_REGISTERS, COPY_SCRATCHPAD, ENCODING_REGISTER, HEALTH_OFFSET,
+    HISTORY_OFFSET, HISTORY_REGISTER, HOLDING_REGISTERS, INPUT_REGISTERS, INPUT_SIZE,
+    INTERVAL_REGISTER, QUERY_OFFSET, QUERY_REGISTER_SIZE, Query, RECALL_EEPROM, RELAY_OFFSET,
+    RELEASE_SLOT, RESCAN_COIL, RESET_STATISTICS, ROM_OFFSET, SAMPLES_REGISTER, SAVE_SETTINGS,
+    STATISTICS_COILS, VALUES_OFFSET, alarm_inputs, encoding::Encoding, exception, history,
+    identification, is_query, read, read_health, read_history, set_history_length, set_interval,
+    window, write, write_query,
+};
+use crate::{relay::Relays, temperature::Handle as Temperature};
+use anyhow::Result;
+use esp_idf_svc::{
+    nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault},
+    sys::EspError,
+};
+use log::{error, info, warn};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, LazyLock, Mutex},
+};
+use tokio::net::TcpListener;
+use tokio_modbus::{
+    prelude::*,
+    server::{
+        Service,
+        tcp::{Server, accept_tcp_connection},
+    },
+};
+
+const NAMESPACE: &str = "modbus";
+const ENCODING: &str = "encoding";
+
+static SOCKET_ADDR: LazyLock<SocketAddr> = LazyLock::new(|| "0.0.0.0:5502".parse().unwrap());
+
+/// Serves the registers, the MAC address identifies the device
+pub async fn run(
+    temperature: Temperature,
+    relays: Relays,
+    nvs: EspDefaultNvsPartition,
+    mac_address: String,
+) -> Result<()> {
+    let mac_address: Arc<str> = mac_address.into();
+    let settings = Arc::new(Mutex::new(Settings::load(EspNvs::new(
+        nvs, NAMESPACE, true,
+    )?)?));
+    let server = Server::new(TcpListener::bind(*SOCKET_ADDR).await?);
+    let new_service = |_socket_addr| {
+        Ok(Some(ExampleService::new(
+            temperature.clone(),
+            relays.clone(),
+            settings.clone(),
+            mac_address.clone(),
+        )))
+    };
+    let on_connected = |stream, socket_addr| async move {
+        accept_tcp_connection(stream, socket_addr, new_service)
+    };
+    let on_process_error = |error| error!("{error}");
+    server.serve(&on_connected, on_process_error).await?;
+    Ok(())
+}
+
+/// Register settings shared by the connections
+struct Settings {
+    encoding: Encoding,
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl Settings {
+    fn load(nvs: EspNvs<NvsDefault>) -> Result<Self, EspError> {
+        let encoding = match nvs.get_u16(ENCODING)? {
+            Some(value) => Encoding::new(value).unwrap_or_else(|| {
+                warn!("Invalid encoding: {value}");
+                Encoding::default()
+            }),
+            None => Encoding::default(),
+        };
+        Ok(Self { encoding, nvs })
+    }
+
+    fn save(&self) -> Result<(), EspError> {
+        self.nvs.set_u16(ENCODING, self.encoding as _)
+    }
+}
+
+struct ExampleService {
+    temperature: Temperature,
+    relays: Relays,
+    settings: Arc<Mutex<Settings>>,
+    query: Arc<Mutex<Query>>,
+    mac_address: Arc<str>,
+}
+
+impl ExampleService {
+    fn new(
+        temperature: Temperature,
+        relays: Relays,
+        settings: Arc<Mutex<Settings>>,
+        mac_address: Arc<str>,
+    ) -> Self {
+        Self {
+            temperature,
+            relays,
+            settings,
+            query: Default::default(),
+            mac_address,
+        }
+    }
+}
+
+impl Service for ExampleService {
+    type Request = Request<'static>;
+    type Response = Response;
+    type Exception = ExceptionCode;
+    type Future = impl Future<Output = Result<Self::Response, Self::Exception>>;
+
+    fn call(&self, request: Self::Request) -> Self::Future {
+        info!("Modbus request: {request:?}");
+        let temperature = self.temperature.clone();
+        let relays = self.relays.clone();
+        let settings = self.settings.clone();
+        let query = self.query.clone();
+        let mac_address = self.mac_address.clone();
+        async move {
+            match request {
+                Request::ReadInputRegisters(address, count) if address >= HISTORY_OFFSET => {
+                    let query = *query.lock().unwrap();
+                    Ok(Response::ReadInputRegisters(
+                        read_history(&temperature, query, address - HISTORY_OFFSET, count).await?,
+                    ))
+                }
+                Request::ReadInputRegisters(address, count) if address >= HEALTH_OFFSET => {
+                    Ok(Response::ReadInputRegisters(read_health(
+                        &temperature,
+                        address - HEALTH_OFFSET,
+                        count,
+                    )?))
+                }
+                Request::ReadInputRegisters(address, count)
+                    if (VALUES_OFFSET..ROM_OFFSET).contains(&address) =>
+                {
+                    let encoding = settings.lock().unwrap().encoding;
+                    Ok(Response::ReadInputRegisters(read(
+                        &temperature,
+                        address - VALUES_OFFSET,
+                        count,
+                        encoding.size(),
+                        |reading| encoding.registers(reading),
+                    )?))
+                }
+                Request::ReadInputRegisters(address, count) => {
+                    let (offset, size, registers) = INPUT_REGISTERS
+                        .into_iter()
+                        .rfind(|&(offset, ..)| offset <= address)
+                        .unwrap();
+                    Ok(Response::ReadInputRegisters(read(
+                        &temperature,
+                        address - offset,
+                        count,
+                        size,
+                        registers,
+                    )?))
+                }
+                Request::ReadDiscreteInputs(address, count) => Ok(Response::ReadDiscreteInputs(
+                    read(&temperature, address, count, INPUT_SIZE, alarm_inputs)?,
+                )),
+                Request::ReadCoils(address, count) if address >= RELAY_OFFSET => {
+                    Ok(Response::ReadCoils(window(
+                        &relays.get(),
+                        address - RELAY_OFFSET,
+                        count,
+                        1,
+                        |&on| vec![on],
+                    )?))
+                }
+                Request::ReadCoils(RESCAN_COIL, 1) => Ok(Response::ReadCoils(vec![false])),
+                Request::ReadCoils(address, count) => {
+                    let (offset, coils) = COILS
+                        .into_iter()
+                        .rfind(|&(offset, _)| offset <= address)
+                        .unwrap();
+                    Ok(Response::ReadCoils(read(
+                        &temperature,
+                        address - offset,
+                        count,
+                        1,
+                        coils,
+                    )?))
+                }
+                Request::WriteSingleCoil(address, value) => {
+                    write_coil(&temperature, &relays, address, value).await?;
+                    Ok(Response::WriteSingleCoil(address, value))
+                }
+                Request::ReadHoldingRegisters(address, count) if is_query(address) => {
+                    let query = *query.lock().unwrap();
+                    Ok(Response::ReadHoldingRegisters(window(
+                        &[query],
+                        address - QUERY_OFFSET,
+                        count,
+                        QUERY_REGISTER_SIZE,
+                        |query| query.registers().to_vec(),
+                    )?))
+                }
+                Request::ReadHoldingRegisters(SAMPLES_REGISTER, 1) => {
+                    let query = *query.lock().unwrap();
+                    let samples = history(&temperature, query).await?;
+                    Ok(Response::ReadHoldingRegisters(vec![
+                        samples.len().min(u16::MAX as _) as _,
+                    ]))
+                }
+                Request::ReadHoldingRegisters(ENCODING_REGISTER, 1) => {
+                    let encoding = settings.lock().unwrap().encoding;
+                    Ok(Response::ReadHoldingRegisters(vec![encoding as _]))
+                }
+                Request::ReadHoldingRegisters(INTERVAL_REGISTER, 1) => {
+                    let interval = temperature.interval().await.map_err(exception)?;
+                    Ok(Response::ReadHoldingRegisters(vec![
+                        interval.as_millis().min(u16::MAX as _) as _,
+                    ]))
+                }
+                Request::ReadHoldingRegisters(HISTORY_REGISTER, 1) => {
+                    let length = temperature.history_length().await.map_err(exception)?;
+                    Ok(Response::ReadHoldingRegisters(vec![length as _]))
+                }
+                Request::ReadHoldingRegisters(address, count) => {
+                    let (offset, size, registers) = HOLDING_REGISTERS
+                        .into_iter()
+                        .rfind(|&(offset, ..)| offset <= address)
+                        .unwrap();
+                    Ok(Response::ReadHoldingRegisters(read(
+                        &temperature,
+                        address - offset,
+                        count,
+                        size,
+                        registers,
+                    )?))
+                }
+                Request::WriteSingleRegister(address, value)
+                    if COMMAND_REGISTERS.contains(&address) =>
+                {
+                    command(&temperature, &settings, address, value).await?;
+                    Ok(Response::WriteSingleRegister(address, value))
+                }
+                Request::WriteMultipleRegisters(address, values)
+                    if COMMAND_REGISTERS.contains(&address) && values.len() == 1 =>
+                {
+                    command(&temperature, &settings, address, values[0]).await?;
+                    Ok(Response::WriteMultipleRegisters(address, 1))
+                }
+                Request::WriteSingleRegister(ENCODING_REGISTER, value) => {
+                    set_encoding(&settings, value)?;
+                    Ok(Response::WriteSingleRegister(ENCODING_REGISTER, value))
+                }
+                Request::WriteMultipleRegisters(ENCODING_REGISTER, values) if values.len() == 1 => {
+                    set_encoding(&settings, values[0])?;
+                    Ok(Response::WriteMultipleRegisters(ENCODING_REGISTER, 1))
+                }
+                Request::WriteSingleRegister(INTERVAL_REGISTER, value) => {
+                    set_interval(&temperature, value).await?;
+                    Ok(Response::WriteSingleRegister(INTERVAL_REGISTER, value))
+                }
+                Request::WriteMultipleRegisters(INTERVAL_REGISTER, values) if values.len() == 1 => {
+                    set_interval(&temperature, values[0]).await?;
+                    Ok(Response::WriteMultipleRegisters(INTERVAL_REGISTER, 1))
+                }
+                Request::WriteSingleRegister(HISTORY_REGISTER, value) => {
+                    set_history_length(&temperature, value).await?;
+                    Ok(Response::WriteSingleRegister(HISTORY_REGISTER, value))
+                }
+                Request::WriteMultipleRegisters(HISTORY_REGISTER, values) if values.len() == 1 => {
+                    set_history_length(&temperature, values[0]).await?;
+                    Ok(Response::WriteMultipleRegisters(HISTORY_REGISTER, 1))
+                }
+                Request::WriteSingleRegister(address, value) if is_query(address) => {
+                    write_query(&query, address - QUERY_OFFSET, &[value])?;
+                    Ok(Response::WriteSingleRegister(address, value))
+                }
+                Request::WriteSingleRegister(address, value) => {
+                    write(&temperature, address, &[value]).await?;
+                    Ok(Response::WriteSingleRegister(address, value))
+                }
+                Request::WriteMultipleRegisters(address, values) if is_query(address) => {
+                    write_query(&query, address - QUERY_OFFSET, &values)?;
+                    Ok(Response::WriteMultipleRegisters(address, values.len() as _))
+                }
+                Request::WriteMultipleRegisters(address, values) => {
+                    write(&temperature, address, &values).await?;
+                    Ok(Response::WriteMultipleRegisters(address, values.len() as _))
+                }
+                Request::Custom(identification::FUNCTION, data) => Ok(Response::Custom(
+                    identification::FUNCTION,
+                    identification::respond(&temperature, &mac_address, &data)?.into(),
+                )),
+                _ => Err(ExceptionCode::IllegalFunction),
+            }
+        }
+    }
+}
+
+async fn command(
+    temperature: &Temperature,
+    settings: &Mutex<Settings>,
+    register: u16,
+    value: u16,
+) -> Result<(), ExceptionCode> {
+    let slot = (value != ALL_SLOTS).then_some(value as _);
+    let result = match register {
+        RESET_STATISTICS => temperature.reset_statistics(slot).await,
+        COPY_SCRATCHPAD => temperature.copy_scratchpad(slot).await.map(|copied| {
+            info!("EEPROMs written: {copied}");
+        }),
+        RECALL_EEPROM => temperature.recall_eeprom(slot).await,
+        SAVE_SETTINGS => match temperature.save().await {
+            Ok(()) => settings.lock().unwrap().save().map_err(Into::into),
+            error => error,
+        },
+        RELEASE_SLOT => temperature.release(slot).await.map(|released| {
+            info!("Slots freed: {released}");
+        }),
+        _ => return Err(ExceptionCode::IllegalDataAddress),
+    };
+    result.map_err(exception)
+}
+
+/// Switches a relay, or acts on a coil written on, writing a trigger off only
+/// checks the address
+async fn write_coil(
+    temperature: &Temperature,
+    relays: &Relays,
+    address: u16,
+    value: bool,
+) -> Result<(), ExceptionCode> {
+    let result = match address {
+        RELAY_OFFSET.. => {
+            return relays
+                .set((address - RELAY_OFFSET) as _, value)
+                .map_err(exception);
+        }
+        RESCAN_COIL.. if address > RESCAN_COIL => return Err(ExceptionCode::IllegalDataAddress),
+        RESCAN_COIL if value => temperature.rescan().await,
+        RESCAN_COIL => Ok(()),
+        STATISTICS_COILS.. => {
+            let slot = (address - STATISTICS_COILS) as usize;
+            match value {
+                true => temperature.reset_statistics(Some(slot)).await,
+                false => temperature.read(slot..slot + 1).map(drop),
+            }
+        }
+        _ => {
+            let slot = address as usize;
+            match value {
+                true => temperature.acknowledge(Some(slot)).await,
+                false => temperature.read(slot..slot + 1).map(drop),
+            }
+        }
+    };
+    result.map_err(exception)
+}
+
+fn set_encoding(settings: &Mutex<Settings>, value: u16) -> Result<(), ExceptionCode> {
+    let encoding = Encoding::new(value).ok_or(ExceptionCode::IllegalDataValue)?;
+    info!("Encoding: {encoding:?}");
+    settings.lock().unwrap().encoding = encoding;
+    Ok(())
+}
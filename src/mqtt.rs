@@ -1,40 +1,69 @@
-use crate::{MAC_ADDRESS, temperature::Request as TemperatureRequest};
-use anyhow::Result;
+use crate::temperature::{Configuration, Handle as Temperature, Limits};
+use anyhow::{Context, Result, bail};
 use esp_idf_svc::{
-    mqtt::client::{EspAsyncMqttClient, EspAsyncMqttConnection, MqttClientConfiguration, QoS},
+    mqtt::client::{
+        EspAsyncMqttClient, EspAsyncMqttConnection, EventPayload, MqttClientConfiguration, QoS,
+    },
     sys::EspError,
 };
 use log::{error, info, trace, warn};
+use std::fmt::Write;
 use tokio::{
-    spawn,
-    sync::{mpsc::Sender, oneshot},
-    time::{Duration, sleep},
+    select, spawn,
+    sync::mpsc,
+    time::{Duration, interval, sleep},
 };
 
 const MQTT_URL: &str = "mqtt://192.168.0.87:1883";
-const MQTT_CLIENT_ID: &str = MAC_ADDRESS;
 const MQTT_USERNAME: Option<&str> = option_env!("MQTT_USERNAME");
 const MQTT_PASSWORD: Option<&str> = option_env!("MQTT_PASSWORD");
 
 const MQTT_TOPIC_BLC: &str = "ippras.ru/blca/#";
 const MQTT_TOPIC_TEMPERATURE: &str = "ippras.ru/blca/temperature";
+/// Retained, ROM addresses of the sensors in alarm
+const MQTT_TOPIC_ALARM: &str = "ippras.ru/blca/temperature/alarm";
+/// Retained, a line per bus: current fault, fault counters and last fault
+const MQTT_TOPIC_HEALTH: &str = "ippras.ru/blca/temperature/health";
+/// A command per message:
+///
+/// - `read <slot> <count>`
+/// - `read-rom <address in hex>`
+/// - `rescan`
+/// - `configure <slot> <resolution in bits> <TH> <TL>`
+/// - `limits <slot> <rate in °C/min> <flatline in s> <band in °C>`, zero
+///   turns a check off
+/// - `inventory`
+/// - `reset-statistics [<slot>]`
+/// - `release [<slot>]`, frees the slot of an absent sensor or of all
+/// - `save`, saves the settings in the NVS
+const MQTT_TOPIC_COMMAND: &str = "ippras.ru/blca/temperature/command";
+/// Response to every command, in the order received
+const MQTT_TOPIC_RESPONSE: &str = "ippras.ru/blca/temperature/response";
 
 const RETRY: Duration = Duration::from_millis(500);
 
-pub(crate) async fn run(
-    mut temperature_sender: Sender<TemperatureRequest>,
-) -> Result<(), EspError> {
+/// Starts the client, identified by the MAC address of the device
+pub fn start(temperature: Temperature, client_id: String) {
+    spawn(async move {
+        if let Err(error) = run(temperature, &client_id).await {
+            error!("MQTT: {error}");
+        }
+    });
+}
+
+pub(crate) async fn run(temperature: Temperature, client_id: &str) -> Result<(), EspError> {
     info!("Initialize MQTT");
     let (mut client, connection) = EspAsyncMqttClient::new(
         MQTT_URL,
         &MqttClientConfiguration {
-            client_id: Some(MQTT_CLIENT_ID),
+            client_id: Some(client_id),
             username: MQTT_USERNAME,
             password: MQTT_PASSWORD,
             ..Default::default()
         },
     )?;
-    spawn(subscriber(connection));
+    let (responses, mut receiver) = mpsc::channel(9);
+    spawn(subscriber(connection, temperature.clone(), responses));
     loop {
         if let Err(error) = client.subscribe(MQTT_TOPIC_BLC, QoS::ExactlyOnce).await {
             warn!(r#"Retry to subscribe to topic "{MQTT_TOPIC_BLC}": {error}"#);
@@ -45,7 +74,7 @@ pub(crate) async fn run(
         // Just to give a chance of our connection to get even the first published message.
         sleep(Duration::from_secs(1)).await;
         loop {
-            if let Err(error) = publisher(&mut client, &mut temperature_sender).await {
+            if let Err(error) = publisher(&mut client, &temperature, &mut receiver).await {
                 error!("{error}");
             }
             sleep(Duration::from_secs(1)).await;
@@ -54,30 +83,135 @@ pub(crate) async fn run(
 }
 
 // Subscriber
-pub(crate) async fn subscriber(mut connection: EspAsyncMqttConnection) {
+pub(crate) async fn subscriber(
+    mut connection: EspAsyncMqttConnection,
+    temperature: Temperature,
+    responses: mpsc::Sender<String>,
+) {
     info!("MQTT subscriber");
     loop {
-        match connection.next().await {
-            Ok(event) => trace!("Subscribed: {}", event.payload()),
+        let command = match connection.next().await {
+            Ok(event) => {
+                trace!("Subscribed: {}", event.payload());
+                match event.payload() {
+                    EventPayload::Received {
+                        topic: Some(MQTT_TOPIC_COMMAND),
+                        data,
+                        ..
+                    } => String::from_utf8_lossy(data).into_owned(),
+                    _ => continue,
+                }
+            }
             Err(error) => {
                 error!("{error}");
                 warn!("MQTT connection closed");
+                continue;
             }
+        };
+        let response = match execute(&temperature, &command).await {
+            Ok(response) => response,
+            Err(error) => {
+                warn!("MQTT command {command:?}: {error}");
+                format!("Error: {error}")
+            }
+        };
+        if responses.send(response).await.is_err() {
+            warn!("MQTT publisher stopped");
         }
     }
 }
 
+/// Executes a command, returns the response
+async fn execute(temperature: &Temperature, command: &str) -> Result<String> {
+    let words: Vec<_> = command.split_whitespace().collect();
+    Ok(match words[..] {
+        ["read", slot, count] => {
+            let slot: usize = slot.parse()?;
+            let end = slot.checked_add(count.parse()?).context("Invalid count")?;
+            let readings = temperature.read(slot..end)?;
+            format!("{readings:?}")
+        }
+        ["read-rom", address] => {
+            let reading = temperature.read_by_rom(u64::from_str_radix(address, 16)?)?;
+            format!("{reading:?}")
+        }
+        ["rescan"] => {
+            temperature.rescan().await?;
+            format!("{:?}", temperature.inventory().await?)
+        }
+        ["configure", slot, bits, high, low] => {
+            let configuration = Configuration::new(bits.parse()?, high.parse()?, low.parse()?)
+                .context("Invalid resolution")?;
+            temperature.configure(slot.parse()?, configuration).await?;
+            format!("{configuration:?}")
+        }
+        ["limits", slot, rate, flatline, band] => {
+            let limits = Limits::new(
+                rate.parse()?,
+                Duration::from_secs(flatline.parse()?),
+                band.parse()?,
+            )
+            .context("Invalid limits")?;
+            temperature.set_limits(slot.parse()?, limits).await?;
+            format!("{limits:?}")
+        }
+        ["inventory"] => format!("{:?}", temperature.inventory().await?),
+        ["reset-statistics"] => {
+            temperature.reset_statistics(None).await?;
+            "Ok".to_owned()
+        }
+        ["reset-statistics", slot] => {
+            temperature.reset_statistics(Some(slot.parse()?)).await?;
+            "Ok".to_owned()
+        }
+        ["release"] => format!("Slots freed: {}", temperature.release(None).await?),
+        ["release", slot] => {
+            format!(
+                "Slots freed: {}",
+                temperature.release(Some(slot.parse()?)).await?
+            )
+        }
+        ["save"] => {
+            temperature.save().await?;
+            "Ok".to_owned()
+        }
+        _ => bail!("Unknown command"),
+    })
+}
+
 // Publisher
 pub(crate) async fn publisher(
     client: &mut EspAsyncMqttClient,
-    temperature_sender: &mut Sender<TemperatureRequest>,
+    temperature: &Temperature,
+    responses: &mut mpsc::Receiver<String>,
 ) -> Result<()> {
     info!("MQTT publisher");
+    // Published on change, starting with the current state
+    let mut published = None;
+    let mut health = None;
+    let mut tick = interval(Duration::from_secs(1));
     loop {
-        let (sender, receiver) = oneshot::channel();
-        temperature_sender.send((0..2, sender)).await?;
-        let temperatures = receiver.await??;
-        let serialized = ron::to_string(&temperatures)?;
+        select! {
+            Some(response) = responses.recv() => {
+                if let Err(error) = client
+                    .publish(MQTT_TOPIC_RESPONSE, QoS::ExactlyOnce, false, response.as_bytes())
+                    .await
+                {
+                    error!("MQTT publish {error:?}");
+                }
+                continue;
+            }
+            _ = tick.tick() => {}
+        }
+        // A line per slot: ROM address, temperature and status
+        let mut serialized = String::new();
+        for reading in temperature.readings().iter() {
+            writeln!(
+                serialized,
+                "{:016x} {} {:?}",
+                reading.address, reading.temperature, reading.status
+            )?;
+        }
         if let Err(error) = client
             .publish(
                 MQTT_TOPIC_TEMPERATURE,
@@ -89,6 +223,55 @@ pub(crate) async fn publisher(
         {
             error!("MQTT publish {error:?}");
         }
-        sleep(Duration::from_secs(1)).await;
+        let alarms = temperature.alarms();
+        if published.as_ref() != Some(&alarms) {
+            let serialized = alarms
+                .iter()
+                .map(|address| format!("{address:016x}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            match client
+                .publish(
+                    MQTT_TOPIC_ALARM,
+                    QoS::ExactlyOnce,
+                    true,
+                    serialized.as_bytes(),
+                )
+                .await
+            {
+                Ok(_) => published = Some(alarms),
+                Err(error) => error!("MQTT publish {error:?}"),
+            }
+        }
+        let current = temperature.health();
+        if health.as_ref() != Some(&current) {
+            let mut serialized = String::new();
+            for (bus, health) in current.iter().enumerate() {
+                let faults = &health.faults;
+                writeln!(
+                    serialized,
+                    "{bus} {:?} {} {} {} {} {} {:?}",
+                    health.current,
+                    faults.no_presence,
+                    faults.short_circuit,
+                    faults.conflict,
+                    faults.timeout,
+                    faults.driver,
+                    health.last,
+                )?;
+            }
+            match client
+                .publish(
+                    MQTT_TOPIC_HEALTH,
+                    QoS::ExactlyOnce,
+                    true,
+                    serialized.as_bytes(),
+                )
+                .await
+            {
+                Ok(_) => health = Some(current),
+                Err(error) => error!("MQTT publish {error:?}"),
+            }
+        }
     }
 }
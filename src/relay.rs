@@ -0,0 +1,72 @@
+use esp_idf_svc::{
+    hal::gpio::{AnyOutputPin, Output, PinDriver},
+    sys::EspError,
+};
+use log::info;
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+use tokio_modbus::prelude::ExceptionCode;
+
+/// Relay outputs, off at start
+#[derive(Clone)]
+pub struct Relays(Arc<Mutex<Vec<PinDriver<'static, AnyOutputPin, Output>>>>);
+
+impl Relays {
+    pub fn new(pins: impl IntoIterator<Item = AnyOutputPin>) -> Result<Self> {
+        let drivers = pins
+            .into_iter()
+            .map(|pin| {
+                let mut driver = PinDriver::output(pin)?;
+                driver.set_level(false.into())?;
+                Ok(driver)
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self(Arc::new(Mutex::new(drivers))))
+    }
+
+    /// Whether the relays are on, in relay order
+    pub(crate) fn get(&self) -> Vec<bool> {
+        let drivers = self.0.lock().unwrap();
+        drivers.iter().map(|driver| driver.is_set_high()).collect()
+    }
+
+    /// Switches a relay on or off
+    pub(crate) fn set(&self, index: usize, on: bool) -> Result<()> {
+        let mut drivers = self.0.lock().unwrap();
+        let expected = 0..drivers.len();
+        let driver = drivers.get_mut(index).ok_or(Error::InvalidIndex {
+            received: index..index + 1,
+            expected,
+        })?;
+        info!("Relay {index}: {on}");
+        driver.set_level(on.into())?;
+        Ok(())
+    }
+}
+
+/// Result
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid index {{ received: {received:?}, expected: {expected:?} }}")]
+    InvalidIndex {
+        received: Range<usize>,
+        expected: Range<usize>,
+    },
+    #[error(transparent)]
+    Esp(#[from] EspError),
+}
+
+impl From<Error> for ExceptionCode {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::InvalidIndex { .. } => ExceptionCode::IllegalDataAddress,
+            Error::Esp(_) => ExceptionCode::ServerDeviceFailure,
+        }
+    }
+}
@@ -1,91 +1,674 @@
-use esp_idf_svc::hal::{gpio::IOPin, onewire::OWAddress, peripheral::Peripheral, rmt::RmtChannel};
-use log::{info, trace};
-use std::{collections::BTreeMap, ops::Range};
-use thermometer::{
-    Ds18b20Driver,
-    scratchpad::{ConfigurationRegister, Resolution, Scratchpad},
+pub(crate) use self::{
+    calibration::Calibration, detection::Limits, filter::Filter, health::Health, history::Sample,
+    settings::Configuration, statistics::Statistics,
+};
+
+use self::{
+    bus::Bus,
+    health::Monitored,
+    reader::{Line, Reader},
+    settings::Settings,
+    slots::Slots,
+    store::Store,
+    worker::Worker,
+};
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::{
+    hal::{gpio::IOPin, peripheral::Peripheral, rmt::RmtChannel},
+    nvs::{EspDefaultNvsPartition, EspNvs},
+    sys::EspError,
+};
+use log::info;
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 use thiserror::Error;
 use tokio::{
     spawn,
-    sync::{
-        mpsc::{self, Sender},
-        oneshot::Sender as OneshotSender,
-    },
+    sync::{broadcast, mpsc, oneshot, watch},
 };
 use tokio_modbus::prelude::ExceptionCode;
 
-pub(crate) type Request = (
-    Range<usize>,
-    OneshotSender<Result<BTreeMap<u64, f32>, Error>>,
-);
+const NAMESPACE: &str = "temperature";
+/// Shortest sample interval
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+/// Samples kept in the histories of all sensors together, 12 bytes a sample
+const HISTORY_BUDGET: usize = 2400;
+
+/// Reading
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Reading {
+    /// ROM address, zero for a free slot
+    pub(crate) address: u64,
+    /// Index of the bus the sensor lives on
+    pub(crate) bus: usize,
+    /// Calibrated temperature, the last valid one unless the status is ok,
+    /// NaN if there is none
+    pub(crate) temperature: f32,
+    /// Temperature as reported by the sensor
+    pub(crate) raw: f32,
+    /// Calibration applied to the raw temperature
+    pub(crate) calibration: Calibration,
+    pub(crate) configuration: Configuration,
+    /// Filtered temperature, NaN until the first valid one
+    pub(crate) filtered: f32,
+    pub(crate) filter: Filter,
+    /// Limits of the rate-of-change and flatline detector
+    pub(crate) limits: Limits,
+    /// Statistics since boot
+    pub(crate) statistics: Statistics,
+    /// Statistics since the last reset
+    pub(crate) window: Statistics,
+    pub(crate) power: Power,
+    pub(crate) status: Status,
+    /// The sensor found its last conversion at or beyond its TH or TL alarm
+    /// trigger, answering the alarm search
+    pub(crate) alarm: bool,
+    /// The alarm was acknowledged, until it clears
+    pub(crate) acknowledged: bool,
+    pub(crate) counters: Counters,
+}
+
+/// Reading status
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(u16)]
+pub enum Status {
+    #[default]
+    Ok = 0,
+    /// Scratchpad failed the CRC check
+    Crc = 1,
+    /// Sensor not found on the bus
+    NotPresent = 2,
+    /// Power-on reset value (85 °C), the conversion did not happen
+    PowerOnReset = 3,
+    /// Sample failed
+    Stale = 4,
+    /// Implausible jump from the previous temperature
+    Implausible = 5,
+    /// Temperature changing faster than the configured rate
+    RateOfChange = 6,
+    /// Temperature stuck for longer than the configured time
+    Flatline = 7,
+    /// Device reports a fault, such as an open or shorted thermocouple
+    DeviceFault = 8,
+}
+
+impl Status {
+    /// Whether the temperature was measured in this sample and counts in the
+    /// statistics and the filter, a rate-of-change fault is a spike recorded
+    /// in the history only
+    pub(crate) fn is_fresh(&self) -> bool {
+        matches!(self, Self::Ok | Self::Flatline)
+    }
+}
+
+/// Power supply of a sensor
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(u16)]
+pub(crate) enum Power {
+    /// Powered through the VDD pin
+    #[default]
+    External = 0,
+    /// Powered from the data line
+    Parasitic = 1,
+}
+
+/// Failure counters since boot
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Counters {
+    /// Scratchpad reads failing the CRC check
+    pub(crate) crc: u32,
+    /// Other failed scratchpad reads
+    pub(crate) failed: u32,
+    /// Power-on reset values
+    pub(crate) power_on_reset: u32,
+    /// Implausible jumps
+    pub(crate) implausible: u32,
+    /// Retried scratchpad reads
+    pub(crate) retries: u32,
+}
+
+/// Reader options
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    /// Sample interval
+    pub interval: Duration,
+    /// Retry policy for failed scratchpad reads
+    pub retry: Retry,
+    /// Largest plausible change between two samples, °C
+    pub jump: f32,
+    /// Samples kept in the history of every sensor, unless set, the budget
+    /// shared by the sensors holding a slot caps it
+    pub history: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            retry: Retry {
+                attempts: 2,
+                backoff: Duration::from_millis(10),
+            },
+            jump: 50.0,
+            history: 300,
+        }
+    }
+}
+
+/// Retry policy
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    /// Retries after the first attempt
+    pub attempts: u32,
+    /// Delay before the first retry, doubled for every next one
+    pub backoff: Duration,
+}
+
+/// Readings cache, the latest reading per sensor in slot order
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Readings(Vec<Reading>);
+
+impl Readings {
+    pub(crate) fn get(&self, indices: Range<usize>) -> Result<&[Reading]> {
+        self.0.get(indices.clone()).ok_or(Error::InvalidIndex {
+            received: indices,
+            expected: 0..self.0.len(),
+        })
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Reading> {
+        self.0.iter()
+    }
+
+    pub(crate) fn as_slice(&self) -> &[Reading] {
+        &self.0
+    }
+}
+
+/// Inventory entry
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Sensor {
+    pub(crate) slot: usize,
+    pub(crate) address: u64,
+    /// Bus the sensor is present on, none while it is absent
+    pub(crate) bus: Option<usize>,
+    /// Power supply, read at discovery
+    pub(crate) power: Power,
+    pub(crate) configuration: Configuration,
+}
+
+/// Sensor event
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// Sensor found on a bus
+    Connected {
+        slot: usize,
+        address: u64,
+        bus: usize,
+    },
+    /// Sensor no longer found on the bus, it keeps its slot
+    Disconnected { slot: usize, address: u64 },
+    /// Sensor flagged by the rate-of-change or flatline detector
+    Fault {
+        slot: usize,
+        address: u64,
+        status: Status,
+    },
+    /// Sensor entering or leaving the alarm
+    Alarm {
+        slot: usize,
+        address: u64,
+        alarm: bool,
+    },
+}
+
+/// Reader command
+///
+/// Every command carries the sender of its typed reply. Readings are served
+/// from the cache and never wait for the reader.
+#[derive(Debug)]
+enum Command {
+    /// Rescans the buses at once
+    Rescan { reply: oneshot::Sender<Result<()>> },
+    /// Changes the configuration of a slot and writes it to the sensor
+    Configure {
+        slot: usize,
+        configuration: Configuration,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Changes the calibration of a slot
+    Calibrate {
+        slot: usize,
+        calibration: Calibration,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Changes the sample interval
+    SetInterval {
+        interval: Duration,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Sample interval
+    Interval { reply: oneshot::Sender<Duration> },
+    /// Changes the number of samples kept in the history of every sensor
+    SetHistoryLength {
+        length: usize,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Number of samples kept in the history of every sensor
+    HistoryLength { reply: oneshot::Sender<usize> },
+    /// Saves the settings in the NVS
+    Save { reply: oneshot::Sender<Result<()>> },
+    /// Sensors holding a slot, present or not
+    Inventory { reply: oneshot::Sender<Vec<Sensor>> },
+    /// Changes the filter of a slot
+    Filter {
+        slot: usize,
+        filter: Filter,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Changes the fault detection limits of a slot
+    Limits {
+        slot: usize,
+        limits: Limits,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Samples of a slot taken within a time range
+    History {
+        slot: usize,
+        timestamps: Range<u32>,
+        reply: oneshot::Sender<Result<Vec<Sample>>>,
+    },
+    /// Acknowledges the alarm, of a slot or of all slots
+    Acknowledge {
+        slot: Option<usize>,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Starts the statistics window over, of a slot or of all slots
+    ResetStatistics {
+        slot: Option<usize>,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Stores the configuration in the sensor EEPROM, of a slot or of all
+    /// slots, replies with the number of EEPROMs written
+    CopyScratchpad {
+        slot: Option<usize>,
+        reply: oneshot::Sender<Result<usize>>,
+    },
+    /// Reloads the configuration from the sensor EEPROM, of a slot or of all
+    /// slots
+    RecallEeprom {
+        slot: Option<usize>,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Frees the slot of an absent sensor or of all absent sensors, replies
+    /// with the number of slots freed
+    Release {
+        slot: Option<usize>,
+        reply: oneshot::Sender<Result<usize>>,
+    },
+}
+
+/// Temperature reader handle
+#[derive(Clone)]
+pub struct Handle {
+    readings: watch::Receiver<Readings>,
+    events: broadcast::Sender<Event>,
+    commands: mpsc::Sender<Command>,
+    /// Health of every bus, recorded by its worker
+    health: Vec<Arc<Mutex<Health>>>,
+}
+
+impl Handle {
+    /// Latest readings
+    pub(crate) fn readings(&self) -> watch::Ref<'_, Readings> {
+        self.readings.borrow()
+    }
+
+    /// Latest readings of the slots
+    pub(crate) fn read(&self, slots: Range<usize>) -> Result<Vec<Reading>> {
+        Ok(self.readings.borrow().get(slots)?.to_vec())
+    }
+
+    /// Latest reading of the sensor
+    pub(crate) fn read_by_rom(&self, address: u64) -> Result<Reading> {
+        self.readings
+            .borrow()
+            .iter()
+            .find(|reading| reading.address == address)
+            .copied()
+            .ok_or(Error::UnknownAddress { address })
+    }
+
+    /// Addresses of the sensors in alarm, in slot order
+    pub(crate) fn alarms(&self) -> Vec<u64> {
+        self.readings
+            .borrow()
+            .iter()
+            .filter(|reading| reading.alarm)
+            .map(|reading| reading.address)
+            .collect()
+    }
+
+    /// Health of every bus, in bus order
+    pub(crate) fn health(&self) -> Vec<Health> {
+        self.health
+            .iter()
+            .map(|health| *health.lock().unwrap())
+            .collect()
+    }
+
+    /// Subscribes to sensor events
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Rescans the buses without waiting for the rescan interval
+    pub(crate) async fn rescan(&self) -> Result<()> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::Rescan { reply })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+
+    /// Changes the configuration of a slot until the next restart and writes
+    /// it to the sensor if present
+    pub(crate) async fn configure(&self, slot: usize, configuration: Configuration) -> Result<()> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::Configure {
+                slot,
+                configuration,
+                reply,
+            })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+
+    /// Changes the calibration of a slot until the next restart, dated now
+    pub(crate) async fn calibrate(&self, slot: usize, calibration: Calibration) -> Result<()> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::Calibrate {
+                slot,
+                calibration,
+                reply,
+            })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+
+    /// Changes the sample interval until the next restart
+    pub(crate) async fn set_interval(&self, interval: Duration) -> Result<()> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::SetInterval { interval, reply })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+
+    pub(crate) async fn interval(&self) -> Result<Duration> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::Interval { reply })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)
+    }
+
+    /// Changes the number of samples kept in the history of every sensor until
+    /// the next restart, a shorter history drops the oldest samples, all
+    /// histories together stay within the sample budget
+    pub(crate) async fn set_history_length(&self, length: usize) -> Result<()> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::SetHistoryLength { length, reply })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+
+    pub(crate) async fn history_length(&self) -> Result<usize> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::HistoryLength { reply })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)
+    }
+
+    /// Saves the settings in the NVS, they survive restarts from then on
+    pub(crate) async fn save(&self) -> Result<()> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::Save { reply })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+
+    /// Sensors holding a slot, in slot order
+    pub(crate) async fn inventory(&self) -> Result<Vec<Sensor>> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::Inventory { reply })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)
+    }
+
+    /// Changes the filter of a slot, the filter state starts over
+    pub(crate) async fn set_filter(&self, slot: usize, filter: Filter) -> Result<()> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::Filter {
+                slot,
+                filter,
+                reply,
+            })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+
+    /// Changes the fault detection limits of a slot, the detector starts
+    /// over
+    pub(crate) async fn set_limits(&self, slot: usize, limits: Limits) -> Result<()> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::Limits {
+                slot,
+                limits,
+                reply,
+            })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+
+    /// Starts the statistics window over, of a slot or of all slots
+    pub(crate) async fn reset_statistics(&self, slot: Option<usize>) -> Result<()> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::ResetStatistics { slot, reply })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+
+    /// Acknowledges the alarm, of a slot or of all slots, a slot out of alarm
+    /// is left alone
+    pub(crate) async fn acknowledge(&self, slot: Option<usize>) -> Result<()> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::Acknowledge { slot, reply })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+
+    /// Stores the configuration in the sensor EEPROM, of a slot or of all
+    /// slots
+    ///
+    /// EEPROMs already holding the configuration are left alone, returns the
+    /// number of EEPROMs written.
+    pub(crate) async fn copy_scratchpad(&self, slot: Option<usize>) -> Result<usize> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::CopyScratchpad { slot, reply })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+
+    /// Reloads the configuration from the sensor EEPROM, of a slot or of all
+    /// slots, the settings take the recalled configuration
+    pub(crate) async fn recall_eeprom(&self, slot: Option<usize>) -> Result<()> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::RecallEeprom { slot, reply })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
 
-pub(super) fn start(
+    /// Frees the slot of an absent sensor, or the slots of all absent sensors
+    ///
+    /// The sensor takes a free slot when found again, its settings are kept.
+    /// Returns the number of slots freed.
+    pub(crate) async fn release(&self, slot: Option<usize>) -> Result<usize> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::Release { slot, reply })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+
+    /// Samples of a slot taken within the range of timestamps (seconds since
+    /// the Unix epoch), oldest first
+    pub(crate) async fn history(&self, slot: usize, timestamps: Range<u32>) -> Result<Vec<Sample>> {
+        let (reply, receiver) = oneshot::channel();
+        self.commands
+            .send(Command::History {
+                slot,
+                timestamps,
+                reply,
+            })
+            .await
+            .map_err(|_| Error::Reader)?;
+        receiver.await.map_err(|_| Error::Reader)?
+    }
+}
+
+/// 1-Wire bus driver
+#[cfg(all(target_os = "espidf", not(feature = "simulator")))]
+pub type Driver = esp_idf_svc::hal::onewire::OWDriver<'static>;
+
+/// Simulated 1-Wire bus driver
+#[cfg(all(target_os = "espidf", feature = "simulator"))]
+pub type Driver = simulator::Simulator;
+
+/// Opens a 1-Wire bus on the pin and RMT channel
+#[cfg(all(target_os = "espidf", not(feature = "simulator")))]
+pub fn driver(
     pin: impl Peripheral<P = impl IOPin> + 'static,
     channel: impl Peripheral<P = impl RmtChannel> + 'static,
-) -> Result<Sender<Request>> {
-    info!("Initialize temperature reader");
-    let mut driver = Ds18b20Driver::new(pin, channel)?;
+) -> Result<Driver> {
+    let driver = Driver::new(pin, channel)?;
     info!("Temperature driver initialized");
-    let mut addresses = driver.search()?.collect::<Result<Vec<OWAddress>, _>>()?;
-    addresses.sort_by_key(OWAddress::address);
-    for address in &addresses {
-        let scratchpad = driver
-            .initialization()?
-            .match_rom(address)?
-            .read_scratchpad()?;
-        info!("{address:x?}: {scratchpad:?}");
-    }
-    for address in &addresses {
-        driver
-            .initialization()?
-            .match_rom(address)?
-            .write_scratchpad(&Scratchpad {
-                alarm_high_trigger_register: 30,
-                alarm_low_trigger_register: 10,
-                configuration_register: ConfigurationRegister {
-                    resolution: Resolution::Twelve,
-                },
-                ..Default::default()
-            })?;
-    }
-    for address in &addresses {
-        let scratchpad = driver
-            .initialization()?
-            .match_rom(address)?
-            .read_scratchpad()?;
-        info!("{address:x?}: {scratchpad:?}");
-    }
-    let (sender, mut receiver) = mpsc::channel::<Request>(9);
+    Ok(driver)
+}
+
+/// Opens a simulated bus, the pins are left untouched
+#[cfg(all(target_os = "espidf", feature = "simulator"))]
+pub fn driver(
+    _pin: impl Peripheral<P = impl IOPin> + 'static,
+    _channel: impl Peripheral<P = impl RmtChannel> + 'static,
+) -> Result<Driver> {
+    Ok(Driver::default())
+}
+
+/// Starts the temperature reader on the buses, the index of a bus in the
+/// list identifies it in the readings
+#[cfg(target_os = "espidf")]
+pub fn start(
+    drivers: Vec<Driver>,
+    nvs: EspDefaultNvsPartition,
+    options: Options,
+) -> Result<Handle> {
+    info!("Initialize temperature reader");
+    #[cfg(feature = "simulator")]
+    let drivers = simulate(drivers);
+    run(drivers, EspNvs::new(nvs, NAMESPACE, true)?, options)
+}
+
+/// Connects the simulated devices to the first bus
+#[cfg(all(target_os = "espidf", feature = "simulator"))]
+fn simulate(mut drivers: Vec<Driver>) -> Vec<Driver> {
+    if let Some(driver) = drivers.first_mut() {
+        driver.insert(0x1A00_0000_4F3B_6C28, 21.5);
+        driver.insert(0x6E00_0000_52A1_9D28, 23.0625);
+        driver.insert(0xB300_0801_9E24_7B10, 19.875);
+        driver.insert(0x4F00_0000_0A16_C33B, 412.25);
+    }
+    drivers
+}
+
+/// Starts the temperature reader on any sensor buses
+///
+/// Every bus is handed over to a dedicated worker thread, so consumers never
+/// touch the buses themselves.
+pub(super) fn run<B: Bus + Send + 'static, S: Store + Send + Sync + 'static>(
+    buses: Vec<B>,
+    nvs: S,
+    options: Options,
+) -> Result<Handle> {
+    let (readings, watcher) = watch::channel(Readings::default());
+    let (events, _) = broadcast::channel(9);
+    let (commands, receiver) = mpsc::channel(9);
+    let health: Vec<_> = buses.iter().map(|_| Arc::default()).collect();
+    let settings = Settings::load(&nvs)?;
+    let reader = Reader {
+        options: Options {
+            interval: settings.interval.unwrap_or(options.interval),
+            history: settings.history.unwrap_or(options.history),
+            ..options
+        },
+        lines: buses
+            .into_iter()
+            .zip(&health)
+            .map(|(bus, health)| {
+                Ok(Line::new(Worker::spawn(Monitored::new(
+                    bus,
+                    Arc::clone(health),
+                ))?))
+            })
+            .collect::<Result<_>>()?,
+        settings,
+        slots: Slots::load(&nvs)?,
+        nvs,
+        readings,
+        events: events.clone(),
+        commands: receiver,
+        powers: Default::default(),
+        channels: Vec::new(),
+    };
     info!("Spawn temperature reader");
-    spawn(async move {
-        while let Some((indices, sender)) = receiver.recv().await {
-            trace!("Read temperatures {indices:?}");
-            let _ = sender.send((|| {
-                trace!("Send temperatures {indices:?}");
-                if indices.end > addresses.len() {
-                    return Err(Error::InvalidIndex {
-                        received: indices,
-                        expected: 0..addresses.len(),
-                    });
-                }
-                let mut temperatures = BTreeMap::new();
-                driver.initialization()?.skip_rom()?.convert_temperature()?;
-                for index in indices {
-                    let address = &addresses[index];
-                    let temperature = driver
-                        .initialization()?
-                        .match_rom(address)?
-                        .read_scratchpad()?
-                        .temperature;
-                    trace!("{address:x?}: {temperature}");
-                    temperatures.insert(address.address(), temperature);
-                }
-                Ok(temperatures)
-            })());
-        }
-    });
-    Ok(sender)
+    spawn(reader.run());
+    Ok(Handle {
+        readings: watcher,
+        events,
+        commands,
+        health,
+    })
 }
 
 /// Result
@@ -99,15 +682,71 @@ pub enum Error {
         received: Range<usize>,
         expected: Range<usize>,
     },
+    #[error("Free slot {{ slot: {slot} }}")]
+    FreeSlot { slot: usize },
+    #[error("Sensor not present {{ slot: {slot} }}")]
+    NotPresent { slot: usize },
+    #[error("Sensor present {{ slot: {slot} }}")]
+    Present { slot: usize },
+    #[error("Unknown sensor {{ address: {address:x?} }}")]
+    UnknownAddress { address: u64 },
+    #[error("Invalid interval {{ received: {received:?}, min: {MIN_INTERVAL:?} }}")]
+    InvalidInterval { received: Duration },
+    #[error("Invalid history length {{ received: {received}, max: {max} }}")]
+    InvalidHistoryLength { received: usize, max: usize },
+    #[error("Too many {key} records {{ count: {count}, max: {max} }}")]
+    TooManyRecords {
+        key: &'static str,
+        count: usize,
+        max: usize,
+    },
     #[error(transparent)]
-    Internal(#[from] thermometer::Error),
+    Bus(#[from] bus::Error),
+    #[cfg(target_os = "espidf")]
+    #[error(transparent)]
+    Esp(#[from] EspError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Temperature worker stopped")]
+    Worker,
+    #[error("Temperature reader stopped")]
+    Reader,
 }
 
 impl From<Error> for ExceptionCode {
     fn from(value: Error) -> Self {
         match value {
-            Error::InvalidIndex { .. } => ExceptionCode::IllegalDataAddress,
-            Error::Internal(_) => ExceptionCode::ServerDeviceFailure,
+            Error::InvalidIndex { .. } | Error::FreeSlot { .. } | Error::UnknownAddress { .. } => {
+                ExceptionCode::IllegalDataAddress
+            }
+            Error::InvalidInterval { .. }
+            | Error::InvalidHistoryLength { .. }
+            | Error::Present { .. } => ExceptionCode::IllegalDataValue,
+            Error::NotPresent { .. }
+            | Error::TooManyRecords { .. }
+            | Error::Bus(_)
+            | Error::Io(_)
+            | Error::Worker
+            | Error::Reader => ExceptionCode::ServerDeviceFailure,
+            #[cfg(target_os = "espidf")]
+            Error::Esp(_) => ExceptionCode::ServerDeviceFailure,
         }
     }
 }
+
+mod bus;
+mod calibration;
+mod detection;
+mod family;
+mod filter;
+mod health;
+mod history;
+mod reader;
+mod scratchpad;
+mod settings;
+#[cfg(any(test, feature = "simulator"))]
+mod simulator;
+mod slots;
+mod statistics;
+mod store;
+mod worker;
@@ -0,0 +1,110 @@
+use super::{
+    Power,
+    family::Family,
+    scratchpad::{self, Scratchpad},
+};
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::sys::EspError;
+use thiserror::Error;
+
+/// ROM command
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Rom {
+    /// Address a single device
+    Match(u64),
+    /// Address all devices at once
+    Skip,
+}
+
+/// Sensor bus
+///
+/// Every transaction starts with an initialization (reset and presence
+/// pulse) followed by the ROM command.
+pub(crate) trait Bus {
+    /// Search ROM
+    fn search(&mut self) -> Result<Vec<u64>>;
+
+    /// Convert T, returns without waiting for the conversion to complete
+    fn convert_temperature(&mut self, rom: Rom) -> Result<()>;
+
+    /// Read time slot following Convert T, whether the conversion is
+    /// complete. Parasitically powered sensors cannot answer.
+    fn is_converted(&mut self) -> Result<bool>;
+
+    /// Read power supply, parasitic if any addressed device is
+    fn read_power_supply(&mut self, rom: Rom) -> Result<Power>;
+
+    /// Copy scratchpad, stores TH, TL and configuration registers in the
+    /// EEPROM. Every copy wears the EEPROM.
+    fn copy_scratchpad(&mut self, rom: Rom) -> Result<()>;
+
+    /// Recall E², reloads TH, TL and configuration registers from the EEPROM
+    fn recall_eeprom(&mut self, rom: Rom) -> Result<()>;
+
+    /// Alarm search along the ROM of the device, whether it takes part: its
+    /// last conversion was at or beyond TH or TL
+    fn alarm_search(&mut self, address: u64) -> Result<bool>;
+
+    /// Read scratchpad, all nine bytes including the CRC
+    fn read_raw(&mut self, address: u64) -> Result<[u8; 9]>;
+
+    /// Write scratchpad, the writable registers of the family
+    fn write_raw(&mut self, rom: Rom, bytes: &[u8]) -> Result<()>;
+
+    /// Read scratchpad, verifying the CRC and decoding by family
+    fn read_scratchpad(&mut self, address: u64) -> Result<Scratchpad> {
+        let family = Family::of(address).ok_or(Error::Family { address })?;
+        let bytes = self.read_raw(address)?;
+        // Nobody answered Match ROM, the pulled-up line reads all ones
+        if bytes == [0xFF; 9] {
+            return Err(Error::Absent { address });
+        }
+        if !scratchpad::verify(&bytes) {
+            return Err(Error::Crc { address });
+        }
+        scratchpad::decode(family, &bytes).ok_or(Error::Fault { address })
+    }
+
+    /// Write scratchpad, addressing all devices writes all three registers
+    fn write_scratchpad(&mut self, rom: Rom, scratchpad: &Scratchpad) -> Result<()> {
+        let bytes = scratchpad::encode(scratchpad);
+        match rom {
+            Rom::Match(address) => match Family::of(address) {
+                Some(Family::Ds18s20) => self.write_raw(rom, &bytes[..2]),
+                Some(Family::Max31850) => Ok(()),
+                _ => self.write_raw(rom, &bytes),
+            },
+            Rom::Skip => self.write_raw(rom, &bytes),
+        }
+    }
+}
+
+/// Result
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("No presence pulse")]
+    NoPresence,
+    #[error("Line stuck low")]
+    ShortCircuit,
+    #[error("Search conflict")]
+    Conflict,
+    #[error("CRC mismatch {{ address: {address:x?} }}")]
+    Crc { address: u64 },
+    #[error("Unsupported family {{ address: {address:x?} }}")]
+    Family { address: u64 },
+    #[error("Device fault {{ address: {address:x?} }}")]
+    Fault { address: u64 },
+    #[error("Device absent {{ address: {address:x?} }}")]
+    Absent { address: u64 },
+    #[error("Timeout")]
+    Timeout,
+    #[cfg(target_os = "espidf")]
+    #[error(transparent)]
+    Driver(EspError),
+}
+
+#[cfg(target_os = "espidf")]
+mod onewire;
@@ -0,0 +1,163 @@
+use super::{Bus, Error, Power, Result, Rom};
+use esp_idf_svc::{
+    hal::onewire::{OWCommand, OWDriver},
+    sys::{ESP_ERR_INVALID_CRC, ESP_ERR_NOT_FOUND, ESP_ERR_TIMEOUT, EspError},
+};
+use std::{thread::sleep, time::Duration};
+
+const CONVERT_T: u8 = 0x44;
+const WRITE_SCRATCHPAD: u8 = 0x4E;
+const READ_SCRATCHPAD: u8 = 0xBE;
+const READ_POWER_SUPPLY: u8 = 0xB4;
+const COPY_SCRATCHPAD: u8 = 0x48;
+const RECALL_EEPROM: u8 = 0xB8;
+const ALARM_SEARCH: u8 = 0xEC;
+/// ROM bytes followed in the alarm search, the CRC byte is all that tells
+/// apart ROMs sharing them, so only the device itself can still take part
+const ALARM_PATH: usize = 7;
+/// EEPROM write time, parasitically powered devices draw it from the line
+const COPY_TIME: Duration = Duration::from_millis(10);
+/// Bytes of read time slots to wait for the recall to complete
+const RECALL_BYTES: usize = 10;
+
+/// The scratchpad is read raw, the DS18B20 driver hides the bytes the CRC is
+/// computed over. The RMT driver has no strong pull-up, parasitically powered
+/// sensors convert on the pull-up resistor alone.
+impl Bus for OWDriver<'_> {
+    /// A line stuck low answers every slot with zero, a ROM of all zeros
+    /// passes the CRC
+    fn search(&mut self) -> Result<Vec<u64>> {
+        OWDriver::search(self)?
+            .map(|address| match address?.address() {
+                0 => Err(Error::ShortCircuit),
+                address => Ok(address),
+            })
+            .collect()
+    }
+
+    fn convert_temperature(&mut self, rom: Rom) -> Result<()> {
+        select(self, rom)?;
+        OWDriver::write(self, &[CONVERT_T])?;
+        Ok(())
+    }
+
+    fn is_converted(&mut self) -> Result<bool> {
+        let mut byte = [0];
+        OWDriver::read(self, &mut byte)?;
+        // Devices hold the line low until done, the last slot is the latest
+        Ok(byte[0] & 0x80 != 0)
+    }
+
+    fn read_power_supply(&mut self, rom: Rom) -> Result<Power> {
+        select(self, rom)?;
+        OWDriver::write(self, &[READ_POWER_SUPPLY])?;
+        let mut byte = [0];
+        OWDriver::read(self, &mut byte)?;
+        // Parasitically powered devices pull the read slot low
+        Ok(if byte[0] & 1 != 0 {
+            Power::External
+        } else {
+            Power::Parasitic
+        })
+    }
+
+    fn copy_scratchpad(&mut self, rom: Rom) -> Result<()> {
+        select(self, rom)?;
+        OWDriver::write(self, &[COPY_SCRATCHPAD])?;
+        // The line must stay idle until the EEPROM is written
+        sleep(COPY_TIME);
+        Ok(())
+    }
+
+    fn recall_eeprom(&mut self, rom: Rom) -> Result<()> {
+        select(self, rom)?;
+        OWDriver::write(self, &[RECALL_EEPROM])?;
+        // Devices hold the line low until done
+        let mut slots = [0; RECALL_BYTES];
+        OWDriver::read(self, &mut slots)?;
+        if slots.iter().all(|&byte| byte & 0x80 == 0) {
+            return Err(Error::Timeout);
+        }
+        Ok(())
+    }
+
+    /// The driver reads and writes whole bytes, a bitwise search is out of
+    /// reach. A read slot is a write-one slot to the devices, so the path of
+    /// a known ROM is written ahead, and the two read slots following it
+    /// answer whether the device is still taking part.
+    fn alarm_search(&mut self, address: u64) -> Result<bool> {
+        OWDriver::reset(self)?;
+        let mut command = [0; 1 + 3 * ALARM_PATH];
+        command[0] = ALARM_SEARCH;
+        // Every ROM bit takes two read slots (the bit and its complement)
+        // and the write slot choosing the direction
+        for bit in 0..8 * ALARM_PATH {
+            for (offset, value) in [true, true, address >> bit & 1 != 0]
+                .into_iter()
+                .enumerate()
+            {
+                let slot = 3 * bit + offset;
+                if value {
+                    command[1 + slot / 8] |= 1 << (slot % 8);
+                }
+            }
+        }
+        OWDriver::write(self, &command)?;
+        let mut byte = [0];
+        OWDriver::read(self, &mut byte)?;
+        // Nobody taking part leaves both slots high, nobody else can pull
+        // both low
+        let expected = address >> (8 * ALARM_PATH) & 1;
+        match byte[0] & 0b11 {
+            0b11 => Ok(false),
+            0b00 => Err(Error::ShortCircuit),
+            slots if slots as u64 == expected | (expected ^ 1) << 1 => Ok(true),
+            _ => Err(Error::Conflict),
+        }
+    }
+
+    fn read_raw(&mut self, address: u64) -> Result<[u8; 9]> {
+        select(self, Rom::Match(address))?;
+        OWDriver::write(self, &[READ_SCRATCHPAD])?;
+        let mut bytes = [0; 9];
+        OWDriver::read(self, &mut bytes)?;
+        // A line stuck low reads all zeros, presence pulse included
+        if bytes == [0; 9] {
+            return Err(Error::ShortCircuit);
+        }
+        Ok(bytes)
+    }
+
+    fn write_raw(&mut self, rom: Rom, bytes: &[u8]) -> Result<()> {
+        select(self, rom)?;
+        OWDriver::write(self, &[&[WRITE_SCRATCHPAD], bytes].concat())?;
+        Ok(())
+    }
+}
+
+/// Initialization followed by the ROM command
+fn select(driver: &OWDriver, rom: Rom) -> Result<()> {
+    driver.reset()?;
+    match rom {
+        Rom::Match(address) => {
+            let mut command = [OWCommand::MatchRom as _; 9];
+            command[1..].copy_from_slice(&address.to_le_bytes());
+            driver.write(&command)?;
+        }
+        Rom::Skip => driver.write(&[OWCommand::SkipRom as _])?,
+    }
+    Ok(())
+}
+
+/// The driver reports a missing presence pulse as not found and a ROM
+/// failing the CRC during the search as an invalid CRC
+impl From<EspError> for Error {
+    fn from(error: EspError) -> Self {
+        match error.code() as u32 {
+            ESP_ERR_NOT_FOUND => Self::NoPresence,
+            ESP_ERR_TIMEOUT => Self::Timeout,
+            ESP_ERR_INVALID_CRC => Self::Conflict,
+            _ => Self::Driver(error),
+        }
+    }
+}
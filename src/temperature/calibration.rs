@@ -0,0 +1,65 @@
+/// Calibration against a reference thermometer
+///
+/// Calibrated temperature is `gain * raw + offset`: a plain offset
+/// calibration keeps unit gain, a two-point calibration fits both.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Calibration {
+    pub(crate) gain: f32,
+    pub(crate) offset: f32,
+    /// Calibration date, seconds since the Unix epoch
+    pub(crate) date: u32,
+    /// Reference thermometer ID
+    pub(crate) reference: u32,
+}
+
+impl Calibration {
+    /// Undated calibration against the reference thermometer, `None` unless
+    /// the gain is a normal number and the offset finite
+    pub(crate) fn new(gain: f32, offset: f32, reference: u32) -> Option<Self> {
+        let calibration = Self {
+            gain,
+            offset,
+            reference,
+            ..Default::default()
+        };
+        calibration.is_valid().then_some(calibration)
+    }
+
+    pub(crate) fn apply(&self, raw: f32) -> f32 {
+        self.gain * raw + self.offset
+    }
+
+    fn is_valid(&self) -> bool {
+        self.gain.is_normal() && self.offset.is_finite()
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8; 16]) -> Option<Self> {
+        let calibration = Self {
+            gain: f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            offset: f32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            date: u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+            reference: u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+        };
+        calibration.is_valid().then_some(calibration)
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[0..4].copy_from_slice(&self.gain.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.date.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.reference.to_le_bytes());
+        bytes
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            offset: 0.0,
+            date: 0,
+            reference: 0,
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use super::Status;
+use std::time::{Duration, Instant};
+
+/// Fault detection limits
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Limits {
+    /// Largest plausible rate of change, °C/min, zero disables the check
+    pub(crate) rate: f32,
+    /// Time without change after which the sensor is considered stuck, zero
+    /// disables the check
+    pub(crate) flatline: Duration,
+    /// Changes within the band do not count as change, °C
+    pub(crate) band: f32,
+}
+
+impl Limits {
+    /// Limits from the rate, °C/min, the flatline time and the band, °C,
+    /// `None` if the rate or the band is negative or not finite
+    pub(crate) fn new(rate: f32, flatline: Duration, band: f32) -> Option<Self> {
+        let limits = Self {
+            rate,
+            flatline,
+            band,
+        };
+        limits.is_valid().then_some(limits)
+    }
+
+    fn is_valid(&self) -> bool {
+        self.rate >= 0.0 && self.band >= 0.0 && self.rate.is_finite() && self.band.is_finite()
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8; 12]) -> Option<Self> {
+        let limits = Self {
+            rate: f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            flatline: Duration::from_secs(u32::from_le_bytes([
+                bytes[4], bytes[5], bytes[6], bytes[7],
+            ]) as _),
+            band: f32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+        };
+        limits.is_valid().then_some(limits)
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; 12] {
+        let mut bytes = [0; 12];
+        bytes[0..4].copy_from_slice(&self.rate.to_le_bytes());
+        bytes[4..8].copy_from_slice(&(self.flatline.as_secs() as u32).to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.band.to_le_bytes());
+        bytes
+    }
+}
+
+/// Rate-of-change and flatline detector of a channel
+#[derive(Clone, Debug)]
+pub(crate) struct Detector {
+    pub(crate) limits: Limits,
+    /// Previous temperature
+    last: Option<(Instant, f32)>,
+    /// Temperature the sensor has stayed around since
+    reference: Option<(Instant, f32)>,
+}
+
+impl Detector {
+    pub(crate) fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            last: None,
+            reference: None,
+        }
+    }
+
+    /// Checks a valid temperature, returns the status flagging a fault
+    pub(crate) fn check(&mut self, temperature: f32, now: Instant) -> Option<Status> {
+        let Limits {
+            rate,
+            flatline,
+            band,
+        } = self.limits;
+        let rate_of_change = self
+            .last
+            .replace((now, temperature))
+            .filter(|&(then, _)| rate > 0.0 && now > then)
+            .is_some_and(|(then, last)| {
+                (temperature - last).abs() / (now - then).as_secs_f32() * 60.0 > rate
+            });
+        let flatlined = match self.reference {
+            Some((since, reference)) if (temperature - reference).abs() <= band => {
+                !flatline.is_zero() && now - since >= flatline
+            }
+            _ => {
+                self.reference = Some((now, temperature));
+                false
+            }
+        };
+        if rate_of_change {
+            Some(Status::RateOfChange)
+        } else if flatlined {
+            Some(Status::Flatline)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn limits() {
+        assert!(Limits::new(-1.0, Duration::ZERO, 0.0).is_none());
+        assert!(Limits::new(1.0, Duration::ZERO, f32::NAN).is_none());
+        let limits = Limits::new(2.0, MINUTE, 0.5).unwrap();
+        assert_eq!(Limits::from_bytes(&limits.to_bytes()), Some(limits));
+    }
+
+    #[test]
+    fn rate_of_change() {
+        let mut detector = Detector::new(Limits::new(2.0, Duration::ZERO, 0.0).unwrap());
+        let start = Instant::now();
+        assert_eq!(detector.check(20.0, start), None);
+        // At the limit
+        assert_eq!(detector.check(22.0, start + MINUTE), None);
+        assert_eq!(
+            detector.check(24.5, start + 2 * MINUTE),
+            Some(Status::RateOfChange)
+        );
+        assert_eq!(detector.check(23.0, start + 3 * MINUTE), None);
+    }
+
+    #[test]
+    fn flatline() {
+        let mut detector = Detector::new(Limits::new(0.0, 2 * MINUTE, 0.1).unwrap());
+        let start = Instant::now();
+        assert_eq!(detector.check(20.0, start), None);
+        // Changes within the band do not count
+        assert_eq!(detector.check(20.05, start + MINUTE), None);
+        assert_eq!(
+            detector.check(19.95, start + 2 * MINUTE),
+            Some(Status::Flatline)
+        );
+        // A change out of the band starts over
+        assert_eq!(detector.check(20.5, start + 3 * MINUTE), None);
+        assert_eq!(detector.check(20.5, start + 4 * MINUTE), None);
+    }
+
+    #[test]
+    fn disabled() {
+        let mut detector = Detector::new(Limits::default());
+        let start = Instant::now();
+        for (minutes, temperature) in [(0, 20.0), (1, 80.0), (60, 80.0)] {
+            assert_eq!(detector.check(temperature, start + minutes * MINUTE), None);
+        }
+    }
+}
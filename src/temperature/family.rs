@@ -0,0 +1,59 @@
+use super::scratchpad::{Resolution, bits};
+use std::time::Duration;
+
+/// Device family, identified by the family code in the lowest ROM byte
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Family {
+    /// 9-bit thermometer, extended resolution from the count registers
+    Ds18s20,
+    /// Programmable resolution thermometer, DS18B20 scratchpad with a lower
+    /// accuracy
+    Ds1822,
+    /// Programmable resolution thermometer
+    Ds18b20,
+    /// Cold-junction compensated thermocouple converter, 0.25 °C
+    Max31850,
+}
+
+impl Family {
+    pub(crate) fn of(address: u64) -> Option<Self> {
+        match address as u8 {
+            0x10 => Some(Self::Ds18s20),
+            0x22 => Some(Self::Ds1822),
+            0x28 => Some(Self::Ds18b20),
+            0x3B => Some(Self::Max31850),
+            _ => None,
+        }
+    }
+
+    /// Whether the resolution can be configured
+    pub(crate) fn has_resolution(&self) -> bool {
+        matches!(self, Self::Ds1822 | Self::Ds18b20)
+    }
+
+    /// Whether the scratchpad holds the TH and TL alarm triggers
+    pub(crate) fn has_alarm(&self) -> bool {
+        !matches!(self, Self::Max31850)
+    }
+
+    /// Whether TH, TL and configuration registers are backed by an EEPROM
+    pub(crate) fn has_eeprom(&self) -> bool {
+        !matches!(self, Self::Max31850)
+    }
+
+    /// Whether the temperature register powers up at 85 °C
+    pub(crate) fn has_power_on_reset(&self) -> bool {
+        !matches!(self, Self::Max31850)
+    }
+
+    /// Maximum conversion time, the resolution applies to the programmable
+    /// families only
+    pub(crate) fn conversion_time(&self, resolution: Resolution) -> Duration {
+        match self {
+            Self::Ds18s20 => Duration::from_millis(750),
+            // 93.75 ms for 9 bits, doubling with every extra bit
+            Self::Ds1822 | Self::Ds18b20 => Duration::from_micros(93_750 << (bits(resolution) - 9)),
+            Self::Max31850 => Duration::from_millis(100),
+        }
+    }
+}
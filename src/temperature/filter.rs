@@ -0,0 +1,167 @@
+use std::{collections::VecDeque, time::Duration};
+
+/// Longest window of the moving average and median filters, in samples
+const MAX_WINDOW: u16 = 32;
+
+/// Signal filter
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum Filter {
+    #[default]
+    None,
+    /// Mean of the last samples
+    MovingAverage { window: u16 },
+    /// Median of the last samples, rejects single-sample spikes
+    Median { window: u16 },
+    /// Exponential smoothing
+    Exponential { time_constant: Duration },
+}
+
+impl Filter {
+    /// Filter from its kind and parameter: the window in samples, or the
+    /// time constant in seconds
+    pub(crate) fn new(kind: u16, parameter: u16) -> Option<Self> {
+        match (kind, parameter) {
+            (0, _) => Some(Self::None),
+            (1, 1..=MAX_WINDOW) => Some(Self::MovingAverage { window: parameter }),
+            (2, 1..=MAX_WINDOW) => Some(Self::Median { window: parameter }),
+            (3, 1..) => Some(Self::Exponential {
+                time_constant: Duration::from_secs(parameter as _),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Kind and parameter
+    pub(crate) fn registers(&self) -> [u16; 2] {
+        match *self {
+            Self::None => [0, 0],
+            Self::MovingAverage { window } => [1, window],
+            Self::Median { window } => [2, window],
+            Self::Exponential { time_constant } => [3, time_constant.as_secs() as _],
+        }
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8; 3]) -> Option<Self> {
+        Self::new(bytes[0] as _, u16::from_le_bytes([bytes[1], bytes[2]]))
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; 3] {
+        let [kind, parameter] = self.registers();
+        let parameter = parameter.to_le_bytes();
+        [kind as _, parameter[0], parameter[1]]
+    }
+}
+
+/// Filter state of a channel
+#[derive(Clone, Debug)]
+pub(crate) struct State {
+    pub(crate) filter: Filter,
+    samples: VecDeque<f32>,
+    value: f32,
+}
+
+impl State {
+    pub(crate) fn new(filter: Filter) -> Self {
+        Self {
+            filter,
+            samples: VecDeque::new(),
+            value: f32::NAN,
+        }
+    }
+
+    /// Latest filtered value, NaN before the first sample
+    pub(crate) fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Feeds a sample taken `interval` after the previous one
+    pub(crate) fn update(&mut self, sample: f32, interval: Duration) -> f32 {
+        self.value = match self.filter {
+            Filter::None => sample,
+            Filter::MovingAverage { window } => {
+                self.push(sample, window);
+                self.samples.iter().sum::<f32>() / self.samples.len() as f32
+            }
+            Filter::Median { window } => {
+                self.push(sample, window);
+                let mut samples: Vec<_> = self.samples.iter().copied().collect();
+                samples.sort_by(f32::total_cmp);
+                let middle = samples.len() / 2;
+                if samples.len() % 2 == 0 {
+                    (samples[middle - 1] + samples[middle]) / 2.0
+                } else {
+                    samples[middle]
+                }
+            }
+            Filter::Exponential { time_constant } if !self.value.is_nan() => {
+                let alpha = 1.0 - (-interval.as_secs_f32() / time_constant.as_secs_f32()).exp();
+                self.value + alpha * (sample - self.value)
+            }
+            Filter::Exponential { .. } => sample,
+        };
+        self.value
+    }
+
+    fn push(&mut self, sample: f32, window: u16) {
+        if self.samples.len() == window as usize {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(1);
+
+    fn filtered(filter: Filter, samples: &[f32]) -> Vec<f32> {
+        let mut state = State::new(filter);
+        samples
+            .iter()
+            .map(|&sample| state.update(sample, INTERVAL))
+            .collect()
+    }
+
+    #[test]
+    fn new() {
+        assert_eq!(Filter::new(1, 4), Some(Filter::MovingAverage { window: 4 }));
+        assert_eq!(Filter::new(1, 0), None);
+        assert_eq!(Filter::new(2, MAX_WINDOW + 1), None);
+        assert_eq!(Filter::new(3, 0), None);
+        assert_eq!(Filter::new(4, 1), None);
+        let filter = Filter::Median { window: 5 };
+        assert_eq!(Filter::from_bytes(&filter.to_bytes()), Some(filter));
+    }
+
+    #[test]
+    fn moving_average() {
+        let filter = Filter::MovingAverage { window: 3 };
+        assert_eq!(
+            filtered(filter, &[1.0, 2.0, 3.0, 7.0]),
+            [1.0, 1.5, 2.0, 4.0]
+        );
+    }
+
+    #[test]
+    fn median() {
+        // A single spike is rejected
+        let filter = Filter::Median { window: 3 };
+        assert_eq!(
+            filtered(filter, &[20.0, 21.0, 90.0, 22.0]),
+            [20.0, 20.5, 21.0, 22.0]
+        );
+    }
+
+    #[test]
+    fn exponential() {
+        let time_constant = Duration::from_secs(10);
+        let filter = Filter::Exponential { time_constant };
+        let values = filtered(filter, &[20.0, 30.0]);
+        // Starts at the first sample
+        assert_eq!(values[0], 20.0);
+        let alpha = 1.0 - (-0.1f32).exp();
+        assert!((values[1] - (20.0 + alpha * 10.0)).abs() < 1e-5);
+    }
+}
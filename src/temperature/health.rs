@@ -0,0 +1,220 @@
+use super::{
+    Power,
+    bus::{Bus, Error, Result, Rom},
+    reader::now,
+    scratchpad::Scratchpad,
+};
+use log::warn;
+use std::sync::{Arc, Mutex};
+
+/// Bus fault class
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u16)]
+pub(crate) enum Fault {
+    /// Nobody answered the reset pulse: the line is open or empty
+    NoPresence = 1,
+    /// Line stuck low, shorted to ground
+    ShortCircuit = 2,
+    /// Devices answering over each other during a search
+    Conflict = 3,
+    /// Devices not completing an operation in time
+    Timeout = 4,
+    /// Other driver failures
+    Driver = 5,
+}
+
+impl Fault {
+    /// Fault of the bus behind the error, errors of a single device are none
+    fn of(error: &Error) -> Option<Self> {
+        match error {
+            Error::NoPresence => Some(Self::NoPresence),
+            Error::ShortCircuit => Some(Self::ShortCircuit),
+            Error::Conflict => Some(Self::Conflict),
+            Error::Timeout => Some(Self::Timeout),
+            #[cfg(target_os = "espidf")]
+            Error::Driver(_) => Some(Self::Driver),
+            Error::Crc { .. }
+            | Error::Family { .. }
+            | Error::Fault { .. }
+            | Error::Absent { .. } => None,
+        }
+    }
+}
+
+/// Fault counters since boot
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Faults {
+    /// Times the bus went empty or open
+    pub(crate) no_presence: u32,
+    pub(crate) short_circuit: u32,
+    pub(crate) conflict: u32,
+    pub(crate) timeout: u32,
+    pub(crate) driver: u32,
+}
+
+/// Health of a bus
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Health {
+    /// Fault of the latest transaction, none if it went through
+    pub(crate) current: Option<Fault>,
+    pub(crate) faults: Faults,
+    /// Last fault and when it happened, seconds since the Unix epoch (zero
+    /// until SNTP has set the clock)
+    pub(crate) last: Option<(Fault, u32)>,
+}
+
+impl Health {
+    fn update<T>(&mut self, result: &Result<T>) {
+        let fault = result.as_ref().err().and_then(Fault::of);
+        // An empty bus is a state, counted once until a device answers
+        if let Some(fault) = fault
+            && (fault != Fault::NoPresence || self.current != Some(fault))
+        {
+            let counter = match fault {
+                Fault::NoPresence => &mut self.faults.no_presence,
+                Fault::ShortCircuit => &mut self.faults.short_circuit,
+                Fault::Conflict => &mut self.faults.conflict,
+                Fault::Timeout => &mut self.faults.timeout,
+                Fault::Driver => &mut self.faults.driver,
+            };
+            *counter += 1;
+            self.last = Some((fault, now().unwrap_or_default()));
+            if self.current != Some(fault) {
+                warn!("Bus fault: {fault:?}");
+            }
+        }
+        self.current = fault;
+    }
+}
+
+/// Bus recording the faults of every transaction in its health
+pub(crate) struct Monitored<B> {
+    bus: B,
+    health: Arc<Mutex<Health>>,
+}
+
+impl<B> Monitored<B> {
+    pub(crate) fn new(bus: B, health: Arc<Mutex<Health>>) -> Self {
+        Self { bus, health }
+    }
+
+    fn record<T>(&self, result: Result<T>) -> Result<T> {
+        self.health.lock().unwrap().update(&result);
+        result
+    }
+}
+
+impl<B: Bus> Bus for Monitored<B> {
+    fn search(&mut self) -> Result<Vec<u64>> {
+        let result = self.bus.search();
+        self.record(result)
+    }
+
+    fn convert_temperature(&mut self, rom: Rom) -> Result<()> {
+        let result = self.bus.convert_temperature(rom);
+        self.record(result)
+    }
+
+    fn is_converted(&mut self) -> Result<bool> {
+        let result = self.bus.is_converted();
+        self.record(result)
+    }
+
+    fn read_power_supply(&mut self, rom: Rom) -> Result<Power> {
+        let result = self.bus.read_power_supply(rom);
+        self.record(result)
+    }
+
+    fn copy_scratchpad(&mut self, rom: Rom) -> Result<()> {
+        let result = self.bus.copy_scratchpad(rom);
+        self.record(result)
+    }
+
+    fn recall_eeprom(&mut self, rom: Rom) -> Result<()> {
+        let result = self.bus.recall_eeprom(rom);
+        self.record(result)
+    }
+
+    fn alarm_search(&mut self, address: u64) -> Result<bool> {
+        let result = self.bus.alarm_search(address);
+        self.record(result)
+    }
+
+    fn read_raw(&mut self, address: u64) -> Result<[u8; 9]> {
+        let result = self.bus.read_raw(address);
+        self.record(result)
+    }
+
+    fn write_raw(&mut self, rom: Rom, bytes: &[u8]) -> Result<()> {
+        let result = self.bus.write_raw(rom, bytes);
+        self.record(result)
+    }
+
+    fn read_scratchpad(&mut self, address: u64) -> Result<Scratchpad> {
+        let result = self.bus.read_scratchpad(address);
+        self.record(result)
+    }
+
+    fn write_scratchpad(&mut self, rom: Rom, scratchpad: &Scratchpad) -> Result<()> {
+        let result = self.bus.write_scratchpad(rom, scratchpad);
+        self.record(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::simulator::Simulator, *};
+
+    const ADDRESS: u64 = 0x1A00_0000_4F3B_6C28;
+
+    #[test]
+    fn faults() {
+        let health = Arc::new(Mutex::new(Health::default()));
+        let simulator = Simulator::default().with_device(ADDRESS, 21.5);
+        let mut bus = Monitored::new(simulator, Arc::clone(&health));
+        bus.bus.inject_timeouts(2);
+        assert!(matches!(
+            bus.convert_temperature(Rom::Skip),
+            Err(Error::Timeout)
+        ));
+        assert!(matches!(bus.read_raw(ADDRESS), Err(Error::Timeout)));
+        assert_eq!(health.lock().unwrap().current, Some(Fault::Timeout));
+        bus.bus.inject_conflicts(1);
+        assert!(matches!(bus.search(), Err(Error::Conflict)));
+        assert_eq!(health.lock().unwrap().last.unwrap().0, Fault::Conflict);
+        // A device error leaves the bus healthy
+        bus.bus.inject_crc_faults(ADDRESS, 1);
+        assert!(matches!(
+            bus.read_scratchpad(ADDRESS),
+            Err(Error::Crc { .. })
+        ));
+        let health = *health.lock().unwrap();
+        assert_eq!(health.current, None);
+        assert_eq!(
+            health.faults,
+            Faults {
+                timeout: 2,
+                conflict: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let health = Arc::new(Mutex::new(Health::default()));
+        let mut bus = Monitored::new(Simulator::default(), Arc::clone(&health));
+        for _ in 0..3 {
+            assert!(matches!(bus.search(), Err(Error::NoPresence)));
+        }
+        assert_eq!(health.lock().unwrap().current, Some(Fault::NoPresence));
+        assert_eq!(health.lock().unwrap().faults.no_presence, 1);
+        // Emptied again
+        bus.bus.insert(ADDRESS, 21.5);
+        assert_eq!(bus.search().unwrap(), [ADDRESS]);
+        assert_eq!(health.lock().unwrap().current, None);
+        bus.bus.remove(ADDRESS);
+        assert!(matches!(bus.search(), Err(Error::NoPresence)));
+        assert_eq!(health.lock().unwrap().faults.no_presence, 2);
+    }
+}
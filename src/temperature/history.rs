@@ -0,0 +1,64 @@
+use super::Status;
+use std::{collections::VecDeque, ops::Range};
+
+/// Timestamped sample
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Sample {
+    /// Seconds since the Unix epoch
+    pub(crate) timestamp: u32,
+    pub(crate) temperature: f32,
+    pub(crate) status: Status,
+}
+
+/// Sample history of a slot, the oldest samples are dropped first
+///
+/// The buffer is allocated up front, pushing never reallocates.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct History {
+    samples: VecDeque<Sample>,
+    capacity: usize,
+}
+
+impl History {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub(crate) fn push(&mut self, sample: Sample) {
+        if self.samples.len() >= self.capacity {
+            self.samples.pop_front();
+        }
+        if self.capacity > 0 {
+            self.samples.push_back(sample);
+        }
+    }
+
+    /// Reallocates for the capacity, a smaller one drops the oldest samples
+    pub(crate) fn resize(&mut self, capacity: usize) {
+        if capacity == self.capacity {
+            return;
+        }
+        if self.samples.len() > capacity {
+            self.samples.drain(..self.samples.len() - capacity);
+        }
+        self.samples.shrink_to(capacity);
+        self.samples.reserve_exact(capacity - self.samples.len());
+        self.capacity = capacity;
+    }
+
+    /// Samples taken within the range, oldest first
+    pub(crate) fn range(&self, timestamps: Range<u32>) -> Vec<Sample> {
+        // Samples are pushed in time order
+        let start = self
+            .samples
+            .partition_point(|sample| sample.timestamp < timestamps.start);
+        self.samples
+            .range(start..)
+            .take_while(|sample| sample.timestamp < timestamps.end)
+            .copied()
+            .collect()
+    }
+}
@@ -0,0 +1,1541 @@
+use super::{
+    Command, Counters, Error, Event, HISTORY_BUDGET, MIN_INTERVAL, Options, Power, Reading,
+    Readings, Result, Retry, Sensor, Status,
+    bus::{self, Bus, Rom},
+    calibration::Calibration,
+    detection::{Detector, Limits},
+    family::Family,
+    filter::{self, Filter},
+    history::{History, Sample},
+    scratchpad::Scratchpad,
+    settings::{Configuration, Settings},
+    slots::Slots,
+    statistics::Statistics,
+    store::Store,
+    worker::Worker,
+};
+use log::{debug, error, info, trace, warn};
+use std::{
+    collections::BTreeMap,
+    ops::Range,
+    thread::sleep,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    select,
+    sync::{broadcast, mpsc, watch},
+    time::{self, MissedTickBehavior},
+};
+
+const RESCAN_INTERVAL: Duration = Duration::from_secs(10);
+/// The clock reads earlier until SNTP has set it (2023-11-14)
+const SYNCHRONIZED: Duration = Duration::from_secs(1_700_000_000);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+const POWER_ON_RESET: f32 = 85.0;
+/// Scratchpad byte 6 after power-on, a genuine DS18B20 conversion sets it to
+/// 16 minus the low temperature bits instead
+const POWER_ON_COUNT_REMAIN: u8 = 0x0C;
+/// A sensor really sitting at 85 °C with the reset count remaining (any
+/// DS18S20 or a clone) is told apart from the reset value by having reached
+/// it from a valid sample within the tolerance
+const POWER_ON_RESET_TOLERANCE: f32 = 1.0;
+
+/// Temperature reader
+///
+/// Owns the sensor inventory. Rescans the buses periodically: newly found
+/// sensors are assigned stable slots and configured from the settings (the
+/// scratchpad is written only when it differs), vanished sensors keep their
+/// slots and read as absent. Slots are shared by all buses. Between rescans
+/// all sensors are converted every sample interval, every bus on its own
+/// worker in parallel, and the results are filtered, recorded in the
+/// history, added to the statistics and published, each with its own status.
+/// Every conversion is followed by an alarm search of the sensors having
+/// alarm triggers.
+pub(super) struct Reader<B, S> {
+    pub(super) options: Options,
+    pub(super) lines: Vec<Line<B>>,
+    pub(super) nvs: S,
+    pub(super) settings: Settings,
+    pub(super) slots: Slots,
+    pub(super) readings: watch::Sender<Readings>,
+    pub(super) events: broadcast::Sender<Event>,
+    pub(super) commands: mpsc::Receiver<Command>,
+    /// Power supply of the sensors, read at discovery
+    pub(super) powers: BTreeMap<u64, Power>,
+    pub(super) channels: Vec<Channel>,
+}
+
+/// 1-Wire line: the worker driving a bus and the sensors present on it
+pub(super) struct Line<B> {
+    worker: Worker<B>,
+    addresses: Vec<u64>,
+    conversion: Conversion,
+}
+
+impl<B> Line<B> {
+    pub(super) fn new(worker: Worker<B>) -> Self {
+        Self {
+            worker,
+            addresses: Vec::new(),
+            conversion: Conversion::default(),
+        }
+    }
+}
+
+/// Conversion timing of a bus
+#[derive(Clone, Copy, Debug, Default)]
+struct Conversion {
+    /// The slowest sensor bounds the conversion of the whole bus
+    time: Duration,
+    /// Parasitically powered sensors draw their power from the idle line and
+    /// cannot signal completion, the line stays idle for the whole time
+    parasitic: bool,
+}
+
+/// Processing state of a slot
+pub(super) struct Channel {
+    /// Sensor the state belongs to
+    address: u64,
+    filter: filter::State,
+    detector: Detector,
+    history: History,
+    /// Statistics since boot
+    statistics: Statistics,
+    /// Statistics since the last reset
+    window: Statistics,
+}
+
+impl Channel {
+    fn new(address: u64, settings: &Settings, history: usize) -> Self {
+        Self {
+            address,
+            filter: filter::State::new(settings.filter(address)),
+            detector: Detector::new(settings.limits(address)),
+            history: History::new(history),
+            statistics: Statistics::default(),
+            window: Statistics::new(now().unwrap_or_default()),
+        }
+    }
+}
+
+impl<B: Bus + Send + 'static, S: Store> Reader<B, S> {
+    pub(super) async fn run(mut self) {
+        let interval = |period| {
+            let mut interval = time::interval(period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        };
+        let mut sample = interval(self.options.interval);
+        let mut rescan = interval(RESCAN_INTERVAL);
+        loop {
+            select! {
+                biased;
+                _ = rescan.tick() => self.rescan().await,
+                _ = sample.tick() => self.sample().await,
+                Some(command) = self.commands.recv() => {
+                    self.command(command).await;
+                    if sample.period() != self.options.interval {
+                        sample = interval(self.options.interval);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn rescan(&mut self) {
+        trace!("Rescan temperature sensors");
+        let mut replies = Vec::new();
+        for line in &self.lines {
+            replies.push(line.worker.submit(search).await);
+        }
+        let mut seen = Vec::new();
+        let mut results = Vec::new();
+        for (bus, reply) in replies.into_iter().enumerate() {
+            let found = match async { reply?.wait().await }
+                .await
+                .and_then(|found| Ok(found?))
+            {
+                Ok(found) => found,
+                Err(error) => {
+                    error!("Rescan bus {bus}: {error}");
+                    continue;
+                }
+            };
+            let (found, unsupported): (Vec<_>, Vec<_>) = found
+                .into_iter()
+                .filter(|address| !seen.contains(address))
+                .partition(|&address| Family::of(address).is_some());
+            if !unsupported.is_empty() {
+                debug!("Unsupported devices on bus {bus}: {unsupported:x?}");
+            }
+            seen.extend_from_slice(&found);
+            results.push((bus, found));
+        }
+        // A sensor moved to another bus is not disconnected
+        for (bus, found) in results {
+            self.update(bus, found, &seen).await;
+        }
+    }
+
+    /// Updates the sensors present on the bus, `seen` on any bus
+    async fn update(&mut self, bus: usize, found: Vec<u64>, seen: &[u64]) {
+        let line = &mut self.lines[bus];
+        for &address in &line.addresses {
+            if !seen.contains(&address)
+                && let Some(slot) = self.slots.slot(address)
+            {
+                let _ = self.events.send(Event::Disconnected { slot, address });
+            }
+        }
+        let added: Vec<_> = found
+            .iter()
+            .copied()
+            .filter(|address| !line.addresses.contains(address))
+            .collect();
+        line.addresses.retain(|address| found.contains(address));
+        if self.slots.assign(&added)
+            && let Err(error) = self.slots.save(&mut self.nvs)
+        {
+            error!("Save slots: {error}");
+        }
+        for address in added {
+            let Some(slot) = self.slots.slot(address) else {
+                continue;
+            };
+            let configuration = self.settings.configuration(address);
+            match line
+                .worker
+                .call(move |bus| configure(bus, address, &configuration))
+                .await
+                .and_then(|configured| Ok(configured?))
+            {
+                Ok(power) => {
+                    self.powers.insert(address, power);
+                    line.addresses.push(address);
+                    let _ = self.events.send(Event::Connected { slot, address, bus });
+                }
+                // Retried on the next rescan
+                Err(error) => error!("Configure {address:x?}: {error}"),
+            }
+        }
+        self.lines[bus].conversion = self.conversion(bus);
+    }
+
+    /// Conversion timing of the sensors present on the bus
+    fn conversion(&self, bus: usize) -> Conversion {
+        let addresses = &self.lines[bus].addresses;
+        Conversion {
+            time: addresses
+                .iter()
+                .filter_map(|&address| {
+                    let family = Family::of(address)?;
+                    Some(self.settings.configuration(address).conversion_time(family))
+                })
+                .max()
+                .unwrap_or_default(),
+            parasitic: addresses
+                .iter()
+                .any(|address| self.powers.get(address) == Some(&Power::Parasitic)),
+        }
+    }
+
+    async fn sample(&mut self) {
+        let previous = self.readings.borrow().clone();
+        let mut replies = Vec::new();
+        for line in &self.lines {
+            let sensors: Vec<_> = line
+                .addresses
+                .iter()
+                .filter_map(|&address| Some((self.slots.slot(address)?, address)))
+                .collect();
+            let settings = self.settings.clone();
+            let previous = previous.clone();
+            let conversion = line.conversion;
+            let options = self.options;
+            replies.push((
+                sensors.clone(),
+                line.worker
+                    .submit(move |bus| {
+                        measure(bus, &sensors, &settings, &previous, &conversion, &options)
+                    })
+                    .await,
+            ));
+        }
+        let previous = |slot: usize, address: Option<u64>| {
+            previous
+                .0
+                .get(slot)
+                .filter(|previous| Some(previous.address) == address)
+        };
+        // Slots of sensors found on no bus read as not present
+        let mut readings: Vec<_> = self
+            .slots
+            .iter()
+            .enumerate()
+            .map(|(slot, address)| unmeasured(address, previous(slot, address), Status::NotPresent))
+            .collect();
+        for (bus, (sensors, reply)) in replies.into_iter().enumerate() {
+            match async { reply?.wait().await }.await {
+                Ok(measured) => {
+                    for (slot, reading) in measured {
+                        readings[slot] = Reading { bus, ..reading };
+                    }
+                }
+                Err(error) => {
+                    error!("Sample bus {bus}: {error}");
+                    for (slot, address) in sensors {
+                        readings[slot] =
+                            unmeasured(Some(address), previous(slot, Some(address)), Status::Stale);
+                    }
+                }
+            }
+        }
+        // Settings follow the sensor, measured or not
+        for reading in readings.iter_mut().filter(|reading| reading.address != 0) {
+            reading.configuration = self.settings.configuration(reading.address);
+            reading.calibration = self.settings.calibration(reading.address);
+        }
+        let mut readings = Readings(readings);
+        self.process(&mut readings);
+        self.readings.send_replace(readings);
+    }
+
+    /// Runs the fault detection, filters the readings, records them in the
+    /// slot histories (once the clock is set) and updates the statistics
+    fn process(&mut self, readings: &mut Readings) {
+        let instant = Instant::now();
+        let now = now();
+        let previous: Vec<_> = self
+            .readings
+            .borrow()
+            .0
+            .iter()
+            .map(|reading| {
+                (
+                    reading.address,
+                    reading.status,
+                    reading.alarm,
+                    reading.acknowledged,
+                )
+            })
+            .collect();
+        let events = self.events.clone();
+        let powers = self.powers.clone();
+        if now.is_none() {
+            trace!("Clock not synchronized, history not recorded");
+        }
+        let interval = self.options.interval;
+        let capacity = self.history_capacity();
+        for (slot, reading) in readings.0.iter_mut().enumerate() {
+            reading.power = powers.get(&reading.address).copied().unwrap_or_default();
+            let channel = self.channel(slot, reading.address);
+            if reading.address != 0 {
+                // Follows the sensors taking and releasing slots
+                channel.history.resize(capacity);
+            }
+            if reading.status == Status::Ok
+                && let Some(status) = channel.detector.check(reading.temperature, instant)
+            {
+                reading.status = status;
+                if previous.get(slot).map(|&(_, status, ..)| status) != Some(status) {
+                    warn!("{:x?}: {status:?}", reading.address);
+                    let _ = events.send(Event::Fault {
+                        slot,
+                        address: reading.address,
+                        status,
+                    });
+                }
+            }
+            let (alarmed, acknowledged) = previous
+                .get(slot)
+                .filter(|&&(address, ..)| address == reading.address)
+                .map_or((false, false), |&(_, _, alarm, acknowledged)| {
+                    (alarm, acknowledged)
+                });
+            reading.acknowledged = reading.alarm && acknowledged;
+            if reading.alarm != alarmed {
+                info!("{:x?}: alarm {}", reading.address, reading.alarm);
+                let _ = events.send(Event::Alarm {
+                    slot,
+                    address: reading.address,
+                    alarm: reading.alarm,
+                });
+            }
+            reading.filter = channel.filter.filter;
+            reading.limits = channel.detector.limits;
+            reading.filtered = if reading.status.is_fresh() {
+                let timestamp = now.unwrap_or_default();
+                channel.statistics.update(reading.temperature, timestamp);
+                channel.window.update(reading.temperature, timestamp);
+                channel.filter.update(reading.temperature, interval)
+            } else {
+                channel.filter.value()
+            };
+            reading.statistics = channel.statistics;
+            reading.window = channel.window;
+            // Free slots and absent sensors have nothing to record
+            if let Some(timestamp) = now
+                && reading.address != 0
+                && reading.status != Status::NotPresent
+            {
+                channel.history.push(Sample {
+                    timestamp,
+                    temperature: reading.temperature,
+                    status: reading.status,
+                });
+            }
+        }
+    }
+
+    /// Channel of the slot, a slot taken over by another sensor starts over
+    fn channel(&mut self, slot: usize, address: u64) -> &mut Channel {
+        if self.channels.get(slot).map(|channel| channel.address) != Some(address) {
+            // Free slots record nothing
+            let history = if address == 0 {
+                0
+            } else {
+                self.history_capacity()
+            };
+            let channel = Channel::new(address, &self.settings, history);
+            if slot < self.channels.len() {
+                self.channels[slot] = channel;
+            } else {
+                self.channels.push(channel);
+            }
+        }
+        &mut self.channels[slot]
+    }
+
+    async fn command(&mut self, command: Command) {
+        match command {
+            Command::Rescan { reply } => {
+                self.rescan().await;
+                let _ = reply.send(Ok(()));
+            }
+            Command::Configure {
+                slot,
+                configuration,
+                reply,
+            } => {
+                let _ = reply.send(self.configure(slot, configuration).await);
+            }
+            Command::Calibrate {
+                slot,
+                calibration,
+                reply,
+            } => {
+                let _ = reply.send(self.calibrate(slot, calibration));
+            }
+            Command::SetInterval { interval, reply } => {
+                let _ = reply.send(self.set_interval(interval));
+            }
+            Command::Interval { reply } => {
+                let _ = reply.send(self.options.interval);
+            }
+            Command::SetHistoryLength { length, reply } => {
+                let _ = reply.send(self.set_history_length(length));
+            }
+            Command::HistoryLength { reply } => {
+                let _ = reply.send(self.options.history);
+            }
+            Command::Save { reply } => {
+                info!("Save settings");
+                let _ = reply.send(self.settings.save(&mut self.nvs, &self.slots));
+            }
+            Command::Inventory { reply } => {
+                let _ = reply.send(self.inventory());
+            }
+            Command::Filter {
+                slot,
+                filter,
+                reply,
+            } => {
+                let _ = reply.send(self.set_filter(slot, filter));
+            }
+            Command::Limits {
+                slot,
+                limits,
+                reply,
+            } => {
+                let _ = reply.send(self.set_limits(slot, limits));
+            }
+            Command::History {
+                slot,
+                timestamps,
+                reply,
+            } => {
+                let _ = reply.send(self.history(slot, timestamps));
+            }
+            Command::Acknowledge { slot, reply } => {
+                let _ = reply.send(self.acknowledge(slot));
+            }
+            Command::ResetStatistics { slot, reply } => {
+                let _ = reply.send(self.reset_statistics(slot));
+            }
+            Command::CopyScratchpad { slot, reply } => {
+                let _ = reply.send(self.copy_scratchpad(slot).await);
+            }
+            Command::RecallEeprom { slot, reply } => {
+                let _ = reply.send(self.recall_eeprom(slot).await);
+            }
+            Command::Release { slot, reply } => {
+                let _ = reply.send(self.release(slot));
+            }
+        }
+    }
+
+    /// Changes the configuration, a present sensor is configured at once
+    async fn configure(&mut self, slot: usize, configuration: Configuration) -> Result<()> {
+        let address = self.address(slot)?;
+        info!("Configure slot {slot}: {configuration:?}");
+        self.settings.set_configuration(address, configuration);
+        self.readings.send_modify(|readings| {
+            if let Some(reading) = readings.0.get_mut(slot) {
+                reading.configuration = configuration;
+            }
+        });
+        let Some(bus) = self
+            .lines
+            .iter()
+            .position(|line| line.addresses.contains(&address))
+        else {
+            return Ok(());
+        };
+        let power = self.lines[bus]
+            .worker
+            .call(move |bus| configure(bus, address, &configuration))
+            .await??;
+        self.powers.insert(address, power);
+        self.lines[bus].conversion = self.conversion(bus);
+        Ok(())
+    }
+
+    /// Changes the calibration, applied from the next sample on
+    fn calibrate(&mut self, slot: usize, calibration: Calibration) -> Result<()> {
+        let address = self.address(slot)?;
+        let calibration = Calibration {
+            date: now().unwrap_or_default(),
+            ..calibration
+        };
+        info!("Calibrate slot {slot}: {calibration:?}");
+        self.settings.set_calibration(address, calibration);
+        self.readings.send_modify(|readings| {
+            if let Some(reading) = readings.0.get_mut(slot) {
+                reading.calibration = calibration;
+            }
+        });
+        Ok(())
+    }
+
+    fn set_interval(&mut self, interval: Duration) -> Result<()> {
+        if interval < MIN_INTERVAL {
+            return Err(Error::InvalidInterval { received: interval });
+        }
+        info!("Sample interval: {interval:?}");
+        self.options.interval = interval;
+        self.settings.interval = Some(interval);
+        Ok(())
+    }
+
+    fn set_history_length(&mut self, length: usize) -> Result<()> {
+        let max = HISTORY_BUDGET / self.occupied().max(1);
+        if length > max {
+            return Err(Error::InvalidHistoryLength {
+                received: length,
+                max,
+            });
+        }
+        info!("History length: {length}");
+        self.options.history = length;
+        self.settings.history = Some(length);
+        let capacity = self.history_capacity();
+        for channel in &mut self.channels {
+            if channel.address != 0 {
+                channel.history.resize(capacity);
+            }
+        }
+        Ok(())
+    }
+
+    /// Samples kept in the history of every sensor, the budget is shared by
+    /// the sensors holding a slot whatever the setting
+    fn history_capacity(&self) -> usize {
+        self.options
+            .history
+            .min(HISTORY_BUDGET / self.occupied().max(1))
+    }
+
+    /// Number of slots held by a sensor
+    fn occupied(&self) -> usize {
+        self.slots.iter().flatten().count()
+    }
+
+    fn inventory(&self) -> Vec<Sensor> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, address)| {
+                let address = address?;
+                Some(Sensor {
+                    slot,
+                    address,
+                    bus: self
+                        .lines
+                        .iter()
+                        .position(|line| line.addresses.contains(&address)),
+                    power: self.powers.get(&address).copied().unwrap_or_default(),
+                    configuration: self.settings.configuration(address),
+                })
+            })
+            .collect()
+    }
+
+    fn history(&self, slot: usize, timestamps: Range<u32>) -> Result<Vec<Sample>> {
+        let address = self.address(slot)?;
+        Ok(self
+            .channels
+            .get(slot)
+            .filter(|channel| channel.address == address)
+            .map(|channel| channel.history.range(timestamps))
+            .unwrap_or_default())
+    }
+
+    /// Acknowledges the alarm, of a slot or of all slots
+    fn acknowledge(&mut self, slot: Option<usize>) -> Result<()> {
+        if let Some(slot) = slot {
+            self.address(slot)?;
+        }
+        info!("Acknowledge alarm {slot:?}");
+        self.readings.send_modify(|readings| {
+            for (_, reading) in readings
+                .0
+                .iter_mut()
+                .enumerate()
+                .filter(|(index, _)| slot.is_none_or(|slot| slot == *index))
+            {
+                reading.acknowledged = reading.alarm;
+            }
+        });
+        Ok(())
+    }
+
+    /// Starts the statistics window over, of a slot or of all slots
+    fn reset_statistics(&mut self, slot: Option<usize>) -> Result<()> {
+        if let Some(slot) = slot {
+            self.address(slot)?;
+        }
+        info!("Reset statistics {slot:?}");
+        let window = Statistics::new(now().unwrap_or_default());
+        let selected = |index: &usize| slot.is_none_or(|slot| slot == *index);
+        for (_, channel) in self
+            .channels
+            .iter_mut()
+            .enumerate()
+            .filter(|(index, _)| selected(index))
+        {
+            channel.window = window;
+        }
+        self.readings.send_modify(|readings| {
+            for (_, reading) in readings
+                .0
+                .iter_mut()
+                .enumerate()
+                .filter(|(index, _)| selected(index))
+            {
+                reading.window = window;
+            }
+        });
+        Ok(())
+    }
+
+    fn set_filter(&mut self, slot: usize, filter: Filter) -> Result<()> {
+        let address = self.address(slot)?;
+        info!("Filter slot {slot}: {filter:?}");
+        self.settings.set_filter(address, filter);
+        if let Some(channel) = self.channels.get_mut(slot) {
+            channel.filter = filter::State::new(filter);
+        }
+        self.readings.send_modify(|readings| {
+            if let Some(reading) = readings.0.get_mut(slot) {
+                reading.filter = filter;
+                reading.filtered = f32::NAN;
+            }
+        });
+        Ok(())
+    }
+
+    fn set_limits(&mut self, slot: usize, limits: Limits) -> Result<()> {
+        let address = self.address(slot)?;
+        info!("Limits slot {slot}: {limits:?}");
+        self.settings.set_limits(address, limits);
+        if let Some(channel) = self.channels.get_mut(slot) {
+            channel.detector = Detector::new(limits);
+        }
+        self.readings.send_modify(|readings| {
+            if let Some(reading) = readings.0.get_mut(slot) {
+                reading.limits = limits;
+            }
+        });
+        Ok(())
+    }
+
+    async fn copy_scratchpad(&self, slot: Option<usize>) -> Result<usize> {
+        let mut copied = 0;
+        for (bus, address) in self.sensors(slot)? {
+            let configuration = self.settings.configuration(address);
+            if self.lines[bus]
+                .worker
+                .call(move |bus| persist(bus, address, &configuration))
+                .await??
+            {
+                copied += 1;
+            }
+        }
+        Ok(copied)
+    }
+
+    /// Reloads the EEPROMs, the settings take the recalled configurations
+    async fn recall_eeprom(&mut self, slot: Option<usize>) -> Result<()> {
+        for (bus, address) in self.sensors(slot)? {
+            let Some(configuration) = self.lines[bus]
+                .worker
+                .call(move |bus| recall(bus, address))
+                .await??
+            else {
+                continue;
+            };
+            if configuration != self.settings.configuration(address) {
+                info!("Recalled {address:x?}: {configuration:?}");
+            }
+            self.settings.set_configuration(address, configuration);
+            if let Some(slot) = self.slots.slot(address) {
+                self.readings.send_modify(|readings| {
+                    if let Some(reading) = readings.0.get_mut(slot) {
+                        reading.configuration = configuration;
+                    }
+                });
+            }
+            self.lines[bus].conversion = self.conversion(bus);
+        }
+        Ok(())
+    }
+
+    /// Frees the slot of an absent sensor or of all absent sensors, returns
+    /// the number of slots freed
+    fn release(&mut self, slot: Option<usize>) -> Result<usize> {
+        let present = |address| {
+            self.lines
+                .iter()
+                .any(|line| line.addresses.contains(&address))
+        };
+        let slots: Vec<_> = match slot {
+            Some(slot) if present(self.address(slot)?) => return Err(Error::Present { slot }),
+            Some(slot) => vec![slot],
+            None => self
+                .slots
+                .iter()
+                .enumerate()
+                .filter_map(|(slot, address)| (!present(address?)).then_some(slot))
+                .collect(),
+        };
+        for &slot in &slots {
+            if let Some(address) = self.slots.release(slot) {
+                self.powers.remove(&address);
+            }
+            self.readings.send_modify(|readings| {
+                if let Some(reading) = readings.0.get_mut(slot) {
+                    *reading = unmeasured(None, None, Status::NotPresent);
+                }
+            });
+        }
+        if !slots.is_empty() {
+            self.slots.save(&mut self.nvs)?;
+        }
+        Ok(slots.len())
+    }
+
+    /// Sensors present in the slot or in all slots, with their buses
+    fn sensors(&self, slot: Option<usize>) -> Result<Vec<(usize, u64)>> {
+        let mut sensors = self
+            .lines
+            .iter()
+            .enumerate()
+            .flat_map(|(bus, line)| line.addresses.iter().map(move |&address| (bus, address)));
+        match slot {
+            Some(slot) => {
+                let address = self.address(slot)?;
+                let sensor = sensors
+                    .find(|&(_, present)| present == address)
+                    .ok_or(Error::NotPresent { slot })?;
+                Ok(vec![sensor])
+            }
+            None => Ok(sensors.collect()),
+        }
+    }
+
+    /// Address of the sensor in the slot
+    fn address(&self, slot: usize) -> Result<u64> {
+        self.slots
+            .iter()
+            .nth(slot)
+            .flatten()
+            .ok_or(Error::FreeSlot { slot })
+    }
+}
+
+/// Seconds since the Unix epoch, once SNTP has set the clock
+pub(super) fn now() -> Option<u32> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    (now >= SYNCHRONIZED).then_some(now.as_secs() as _)
+}
+
+/// An empty bus answers no presence pulse
+fn search(bus: &mut impl Bus) -> bus::Result<Vec<u64>> {
+    match bus.search() {
+        Err(bus::Error::NoPresence) => Ok(Vec::new()),
+        found => found,
+    }
+}
+
+/// Configures the sensor, returns its power supply
+fn configure(
+    bus: &mut impl Bus,
+    address: u64,
+    configuration: &Configuration,
+) -> bus::Result<Power> {
+    let family = Family::of(address).ok_or(bus::Error::Family { address })?;
+    let power = bus.read_power_supply(Rom::Match(address))?;
+    let scratchpad = bus.read_scratchpad(address)?;
+    info!("{address:x?}: {family:?} {power:?} {scratchpad:?}");
+    if !configuration.matches(family, &scratchpad) {
+        info!("Configure {address:x?}: {configuration:?}");
+        bus.write_scratchpad(Rom::Match(address), &configuration.scratchpad())?;
+    }
+    Ok(power)
+}
+
+/// Stores the configuration in the EEPROM, returns whether it had to be
+/// written
+///
+/// The EEPROM is recalled and compared first, every copy wears it.
+fn persist(bus: &mut impl Bus, address: u64, configuration: &Configuration) -> bus::Result<bool> {
+    let family = Family::of(address).ok_or(bus::Error::Family { address })?;
+    if !family.has_eeprom() {
+        return Ok(false);
+    }
+    // Overwrites the scratchpad, which is written back below if it differs
+    bus.recall_eeprom(Rom::Match(address))?;
+    if configuration.matches(family, &bus.read_scratchpad(address)?) {
+        debug!("EEPROM of {address:x?} up to date");
+        return Ok(false);
+    }
+    info!("Copy scratchpad {address:x?}: {configuration:?}");
+    bus.write_scratchpad(Rom::Match(address), &configuration.scratchpad())?;
+    bus.copy_scratchpad(Rom::Match(address))?;
+    Ok(true)
+}
+
+/// Reloads the scratchpad from the EEPROM, returns the recalled
+/// configuration, none without an EEPROM
+fn recall(bus: &mut impl Bus, address: u64) -> bus::Result<Option<Configuration>> {
+    let family = Family::of(address).ok_or(bus::Error::Family { address })?;
+    if !family.has_eeprom() {
+        return Ok(None);
+    }
+    bus.recall_eeprom(Rom::Match(address))?;
+    let scratchpad = bus.read_scratchpad(address)?;
+    info!("Recall EEPROM {address:x?}: {scratchpad:?}");
+    let configuration = Configuration::from_scratchpad(&scratchpad);
+    if configuration.is_none() {
+        warn!("{address:x?} recalled TL above TH");
+    }
+    Ok(configuration)
+}
+
+/// Waits for the conversion, externally powered sensors are polled for
+/// completion
+fn wait(bus: &mut impl Bus, conversion: &Conversion) {
+    if conversion.parasitic {
+        sleep(conversion.time);
+        return;
+    }
+    let start = Instant::now();
+    while start.elapsed() < conversion.time {
+        sleep(POLL_INTERVAL.min(conversion.time.saturating_sub(start.elapsed())));
+        match bus.is_converted() {
+            Ok(true) => return,
+            Ok(false) => {}
+            Err(error) => {
+                warn!("Poll conversion: {error}");
+                sleep(conversion.time.saturating_sub(start.elapsed()));
+                return;
+            }
+        }
+    }
+}
+
+/// Measures the sensors of the bus, by slot
+fn measure(
+    bus: &mut impl Bus,
+    sensors: &[(usize, u64)],
+    settings: &Settings,
+    previous: &Readings,
+    conversion: &Conversion,
+    options: &Options,
+) -> Vec<(usize, Reading)> {
+    trace!("Sample temperatures");
+    // An empty bus answers no presence pulse
+    let converted = sensors.is_empty()
+        || match bus.convert_temperature(Rom::Skip) {
+            Ok(()) => {
+                wait(bus, conversion);
+                true
+            }
+            Err(error) => {
+                warn!("Convert temperature: {error}");
+                false
+            }
+        };
+    sensors
+        .iter()
+        .map(|&(slot, address)| {
+            let previous = previous
+                .0
+                .get(slot)
+                .filter(|previous| previous.address == address);
+            let mut counters = previous
+                .map(|previous| previous.counters)
+                .unwrap_or_default();
+            // Validation works on raw temperatures
+            let last = previous.map_or(f32::NAN, |previous| previous.raw);
+            let (raw, status) = if !converted {
+                (last, Status::Stale)
+            } else {
+                match read(bus, address, &options.retry, &mut counters) {
+                    Ok(scratchpad)
+                        if scratchpad.temperature == POWER_ON_RESET
+                            && scratchpad.count_remain == POWER_ON_COUNT_REMAIN
+                            && Family::of(address)
+                                .is_some_and(|family| family.has_power_on_reset())
+                            && !previous.is_some_and(|previous| {
+                                previous.status == Status::Ok
+                                    && (POWER_ON_RESET - previous.raw).abs()
+                                        <= POWER_ON_RESET_TOLERANCE
+                            }) =>
+                    {
+                        counters.power_on_reset += 1;
+                        (last, Status::PowerOnReset)
+                    }
+                    // A jump persisting over two samples is accepted
+                    Ok(Scratchpad { temperature, .. })
+                        if (temperature - last).abs() > options.jump
+                            && previous.map(|previous| previous.status)
+                                != Some(Status::Implausible) =>
+                    {
+                        counters.implausible += 1;
+                        (last, Status::Implausible)
+                    }
+                    Ok(Scratchpad { temperature, .. }) => (temperature, Status::Ok),
+                    Err(error) => {
+                        warn!("Read scratchpad {address:x?}: {error}");
+                        (last, status(&error))
+                    }
+                }
+            };
+            // Without a conversion the sensor keeps its alarm flag
+            let last_alarm = previous.is_some_and(|previous| previous.alarm);
+            let alarm = if converted {
+                alarm_search(bus, address).unwrap_or_else(|error| {
+                    warn!("Alarm search {address:x?}: {error}");
+                    last_alarm
+                })
+            } else {
+                last_alarm
+            };
+            let calibration = settings.calibration(address);
+            let temperature = calibration.apply(raw);
+            trace!("{address:x?}: {temperature} ({raw}) {status:?}");
+            let reading = Reading {
+                temperature,
+                raw,
+                calibration,
+                status,
+                alarm,
+                counters,
+                ..unmeasured(Some(address), None, status)
+            };
+            (slot, reading)
+        })
+        .collect()
+}
+
+/// Reading without a measurement, a failed sample keeps the last
+/// temperatures
+fn unmeasured(address: Option<u64>, previous: Option<&Reading>, status: Status) -> Reading {
+    match previous {
+        Some(previous) if status != Status::NotPresent => Reading {
+            status,
+            ..*previous
+        },
+        _ => Reading {
+            address: address.unwrap_or_default(),
+            bus: previous.map_or(0, |previous| previous.bus),
+            temperature: f32::NAN,
+            raw: f32::NAN,
+            calibration: Calibration::default(),
+            configuration: Configuration::default(),
+            // Filled in by the reader
+            filtered: f32::NAN,
+            filter: Filter::None,
+            limits: Limits::default(),
+            statistics: Statistics::default(),
+            window: Statistics::default(),
+            power: Power::default(),
+            status,
+            alarm: false,
+            acknowledged: false,
+            counters: previous
+                .map(|previous| previous.counters)
+                .unwrap_or_default(),
+        },
+    }
+}
+
+/// Whether the sensor is in alarm, families without alarm triggers never are
+fn alarm_search(bus: &mut impl Bus, address: u64) -> bus::Result<bool> {
+    if !Family::of(address).is_some_and(|family| family.has_alarm()) {
+        return Ok(false);
+    }
+    bus.alarm_search(address)
+}
+
+/// Reads the scratchpad, retrying with backoff
+fn read(
+    bus: &mut impl Bus,
+    address: u64,
+    retry: &Retry,
+    counters: &mut Counters,
+) -> bus::Result<Scratchpad> {
+    let mut attempt = 0;
+    loop {
+        let error = match bus.read_scratchpad(address) {
+            Ok(scratchpad) => return Ok(scratchpad),
+            Err(error) => error,
+        };
+        match error {
+            bus::Error::Crc { .. } => counters.crc += 1,
+            // Reported by the device itself or gone, a retry reads the same
+            bus::Error::Family { .. } | bus::Error::Fault { .. } | bus::Error::Absent { .. } => {
+                return Err(error);
+            }
+            _ => counters.failed += 1,
+        }
+        if attempt == retry.attempts {
+            return Err(error);
+        }
+        sleep(retry.backoff * 2u32.pow(attempt));
+        attempt += 1;
+        counters.retries += 1;
+    }
+}
+
+fn status(error: &bus::Error) -> Status {
+    match error {
+        bus::Error::Crc { .. } => Status::Crc,
+        bus::Error::NoPresence | bus::Error::Absent { .. } => Status::NotPresent,
+        bus::Error::Fault { .. } => Status::DeviceFault,
+        bus::Error::Family { .. }
+        | bus::Error::ShortCircuit
+        | bus::Error::Conflict
+        | bus::Error::Timeout => Status::Stale,
+        #[cfg(target_os = "espidf")]
+        bus::Error::Driver(_) => Status::Stale,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{scratchpad::Resolution, simulator::Simulator, store::Memory},
+        *,
+    };
+    use crate::temperature::run;
+
+    const FIRST: u64 = 0x1A00_0000_4F3B_6C28;
+    const SECOND: u64 = 0x6E00_0000_52A1_9D28;
+
+    /// Reader on a single simulated bus, with the worker driving it and a
+    /// subscription to its events
+    fn reader(
+        simulator: Simulator,
+    ) -> (
+        Reader<Simulator, Memory>,
+        Worker<Simulator>,
+        broadcast::Receiver<Event>,
+    ) {
+        let worker = Worker::spawn(simulator).unwrap();
+        let (readings, _) = watch::channel(Readings::default());
+        let (events, receiver) = broadcast::channel(9);
+        let (_, commands) = mpsc::channel(1);
+        let reader = Reader {
+            options: Options {
+                retry: Retry {
+                    attempts: 2,
+                    backoff: Duration::ZERO,
+                },
+                ..Default::default()
+            },
+            lines: vec![Line::new(worker.clone())],
+            nvs: Memory::default(),
+            settings: Settings::default(),
+            slots: Slots::default(),
+            readings,
+            events,
+            commands,
+            powers: BTreeMap::new(),
+            channels: Vec::new(),
+        };
+        (reader, worker, receiver)
+    }
+
+    fn reading(reader: &Reader<Simulator, Memory>, slot: usize) -> Reading {
+        reader.readings.borrow().get(slot..slot + 1).unwrap()[0]
+    }
+
+    #[tokio::test]
+    async fn search_and_configure() {
+        let simulator = Simulator::default()
+            .with_device(FIRST, 21.5)
+            .with_device(SECOND, 23.0625);
+        let (mut reader, worker, mut events) = reader(simulator);
+        let configuration = Configuration::new(9, 40, -5).unwrap();
+        reader.settings.set_configuration(SECOND, configuration);
+        worker
+            .call(|bus| bus.set_power(FIRST, Power::Parasitic))
+            .await
+            .unwrap();
+        reader.rescan().await;
+        assert_eq!(
+            reader.slots.iter().collect::<Vec<_>>(),
+            [Some(FIRST), Some(SECOND)]
+        );
+        for (slot, address) in [(0, FIRST), (1, SECOND)] {
+            assert_eq!(
+                events.try_recv().unwrap(),
+                Event::Connected {
+                    slot,
+                    address,
+                    bus: 0
+                }
+            );
+        }
+        // Only the sensor differing from its settings is written
+        let scratchpad = worker
+            .call(|bus| bus.read_scratchpad(SECOND))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            scratchpad.configuration_register.resolution,
+            Resolution::Nine
+        );
+        assert_eq!(scratchpad.alarm_high_trigger_register, 40);
+        assert_eq!(scratchpad.alarm_low_trigger_register, -5);
+        reader.sample().await;
+        let first = reading(&reader, 0);
+        assert_eq!(first.status, Status::Ok);
+        assert_eq!(first.temperature, 21.5);
+        assert_eq!(first.power, Power::Parasitic);
+        let second = reading(&reader, 1);
+        assert_eq!(second.status, Status::Ok);
+        assert_eq!(second.temperature, 23.0);
+        assert_eq!(second.configuration, configuration);
+        // Reconfigured at once, the conversion follows the new resolution
+        let configuration = Configuration::new(12, 40, -5).unwrap();
+        reader.configure(1, configuration).await.unwrap();
+        worker
+            .call(|bus| bus.set_temperature(SECOND, 23.0625))
+            .await
+            .unwrap();
+        reader.sample().await;
+        assert_eq!(reading(&reader, 1).temperature, 23.0625);
+        assert!(matches!(
+            reader.configure(2, configuration).await,
+            Err(Error::FreeSlot { slot: 2 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn invalid_index() {
+        let simulator = Simulator::default().with_device(FIRST, 21.5);
+        let handle = run(vec![simulator], Memory::default(), Options::default()).unwrap();
+        handle
+            .readings
+            .clone()
+            .wait_for(|readings| readings.iter().next().is_some())
+            .await
+            .unwrap();
+        assert_eq!(handle.read(0..1).unwrap()[0].address, FIRST);
+        assert!(matches!(
+            handle.read(0..2),
+            Err(Error::InvalidIndex {
+                received,
+                expected,
+            }) if received == (0..2) && expected == (0..1)
+        ));
+        assert!(matches!(
+            handle.read_by_rom(SECOND),
+            Err(Error::UnknownAddress { address: SECOND })
+        ));
+    }
+
+    #[tokio::test]
+    async fn crc_faults() {
+        let simulator = Simulator::default().with_device(FIRST, 21.5);
+        let (mut reader, worker, _) = reader(simulator);
+        reader.rescan().await;
+        reader.sample().await;
+        // A single fault is retried
+        worker
+            .call(|bus| bus.inject_crc_faults(FIRST, 1))
+            .await
+            .unwrap();
+        reader.sample().await;
+        let retried = reading(&reader, 0);
+        assert_eq!(retried.status, Status::Ok);
+        assert_eq!(retried.temperature, 21.5);
+        assert_eq!(retried.counters.crc, 1);
+        assert_eq!(retried.counters.retries, 1);
+        // Faults outlasting the retries keep the last temperature
+        worker
+            .call(|bus| {
+                bus.set_temperature(FIRST, 30.0);
+                bus.inject_crc_faults(FIRST, 3);
+            })
+            .await
+            .unwrap();
+        reader.sample().await;
+        let failed = reading(&reader, 0);
+        assert_eq!(failed.status, Status::Crc);
+        assert_eq!(failed.temperature, 21.5);
+        assert_eq!(failed.counters.crc, 4);
+        assert_eq!(failed.counters.retries, 3);
+        reader.sample().await;
+        let recovered = reading(&reader, 0);
+        assert_eq!(recovered.status, Status::Ok);
+        assert_eq!(recovered.temperature, 30.0);
+    }
+
+    #[tokio::test]
+    async fn removed_device() {
+        let simulator = Simulator::default()
+            .with_device(FIRST, 21.5)
+            .with_device(SECOND, 23.0);
+        let (mut reader, worker, mut events) = reader(simulator);
+        reader.rescan().await;
+        reader.sample().await;
+        while events.try_recv().is_ok() {}
+        let configuration = Configuration::new(10, 50, 0).unwrap();
+        reader.configure(0, configuration).await.unwrap();
+        let calibration = Calibration::new(1.0, 0.5, 7).unwrap();
+        reader.calibrate(0, calibration).unwrap();
+        let calibration = reader.settings.calibration(FIRST);
+        assert!(worker.call(|bus| bus.remove(FIRST)).await.unwrap());
+        // Read as absent until the next rescan, not as a failed read
+        reader.sample().await;
+        let gone = reading(&reader, 0);
+        assert_eq!(gone.status, Status::NotPresent);
+        assert_eq!(gone.counters, Counters::default());
+        reader.rescan().await;
+        assert_eq!(
+            events.try_recv().unwrap(),
+            Event::Disconnected {
+                slot: 0,
+                address: FIRST
+            }
+        );
+        reader.sample().await;
+        // The sensor keeps its slot
+        let absent = reading(&reader, 0);
+        assert_eq!(absent.address, FIRST);
+        assert_eq!(absent.status, Status::NotPresent);
+        assert!(absent.temperature.is_nan());
+        // with its settings
+        assert_eq!(absent.configuration, configuration);
+        assert_eq!(absent.calibration, calibration);
+        assert_eq!(reading(&reader, 1).status, Status::Ok);
+        assert!(matches!(
+            reader.sensors(Some(0)),
+            Err(Error::NotPresent { slot: 0 })
+        ));
+        // and takes it back when found again
+        worker.call(|bus| bus.insert(FIRST, 22.0)).await.unwrap();
+        reader.rescan().await;
+        reader.sample().await;
+        let found = reading(&reader, 0);
+        assert_eq!(found.status, Status::Ok);
+        assert_eq!(found.raw, 22.0);
+        assert_eq!(found.temperature, 22.5);
+    }
+
+    #[tokio::test]
+    async fn moved() {
+        let (mut reader, first, mut events) = reader(Simulator::default());
+        let second = Worker::spawn(Simulator::default().with_device(FIRST, 21.5)).unwrap();
+        reader.lines.push(Line::new(second.clone()));
+        reader.rescan().await;
+        assert_eq!(
+            events.try_recv().unwrap(),
+            Event::Connected {
+                slot: 0,
+                address: FIRST,
+                bus: 1
+            }
+        );
+        // Moved to the first bus, found there before missed on the second
+        assert!(second.call(|bus| bus.remove(FIRST)).await.unwrap());
+        first.call(|bus| bus.insert(FIRST, 21.5)).await.unwrap();
+        reader.rescan().await;
+        assert_eq!(
+            events.try_recv().unwrap(),
+            Event::Connected {
+                slot: 0,
+                address: FIRST,
+                bus: 0
+            }
+        );
+        assert!(events.try_recv().is_err());
+        reader.sample().await;
+        assert_eq!(reading(&reader, 0).status, Status::Ok);
+    }
+
+    #[tokio::test]
+    async fn release() {
+        let simulator = Simulator::default()
+            .with_device(FIRST, 21.5)
+            .with_device(SECOND, 23.0);
+        let (mut reader, worker, _) = reader(simulator);
+        reader.rescan().await;
+        assert!(matches!(
+            reader.release(Some(0)),
+            Err(Error::Present { slot: 0 })
+        ));
+        assert!(worker.call(|bus| bus.remove(FIRST)).await.unwrap());
+        reader.rescan().await;
+        assert_eq!(reader.release(None).unwrap(), 1);
+        reader.sample().await;
+        assert_eq!(reading(&reader, 0).address, 0);
+        assert_eq!(reading(&reader, 1).address, SECOND);
+        assert!(matches!(
+            reader.release(Some(0)),
+            Err(Error::FreeSlot { slot: 0 })
+        ));
+        assert_eq!(Slots::load(&reader.nvs).unwrap(), reader.slots);
+        // A sensor found again takes a free slot
+        worker.call(|bus| bus.insert(FIRST, 22.0)).await.unwrap();
+        reader.rescan().await;
+        reader.sample().await;
+        assert_eq!(reading(&reader, 0).address, FIRST);
+    }
+
+    #[tokio::test]
+    async fn history() {
+        let simulator = Simulator::default()
+            .with_device(FIRST, 21.5)
+            .with_device(SECOND, 23.0);
+        let (mut reader, worker, _) = reader(simulator);
+        reader.rescan().await;
+        for _ in 0..3 {
+            reader.sample().await;
+        }
+        let samples = |reader: &Reader<_, _>, slot| reader.history(slot, 0..u32::MAX).unwrap();
+        assert_eq!(samples(&reader, 0).len(), 3);
+        // Absent sensors and free slots record nothing
+        assert!(worker.call(|bus| bus.remove(SECOND)).await.unwrap());
+        reader.rescan().await;
+        reader.sample().await;
+        assert_eq!(samples(&reader, 0).len(), 4);
+        assert_eq!(samples(&reader, 1).len(), 3);
+        assert_eq!(reader.release(Some(1)).unwrap(), 1);
+        reader.sample().await;
+        assert!(reader.channels[1].history.range(0..u32::MAX).is_empty());
+        // A shorter history drops the oldest samples
+        let recorded = samples(&reader, 0);
+        let latest = recorded[recorded.len() - 2..].to_vec();
+        reader.set_history_length(2).unwrap();
+        assert_eq!(samples(&reader, 0), latest);
+        assert_eq!(reader.settings.history, Some(2));
+        // The budget is shared by the sensors holding a slot
+        assert!(matches!(
+            reader.set_history_length(HISTORY_BUDGET + 1),
+            Err(Error::InvalidHistoryLength { .. })
+        ));
+        reader.set_history_length(HISTORY_BUDGET).unwrap();
+        worker.call(|bus| bus.insert(SECOND, 23.0)).await.unwrap();
+        reader.rescan().await;
+        reader.sample().await;
+        assert_eq!(reader.history_capacity(), HISTORY_BUDGET / 2);
+        assert!(matches!(
+            reader.set_history_length(HISTORY_BUDGET / 2 + 1),
+            Err(Error::InvalidHistoryLength { max, .. }) if max == HISTORY_BUDGET / 2
+        ));
+    }
+
+    #[tokio::test]
+    async fn reset_statistics() {
+        let simulator = Simulator::default()
+            .with_device(FIRST, 21.5)
+            .with_device(SECOND, 23.0);
+        let (mut reader, worker, _) = reader(simulator);
+        reader.rescan().await;
+        reader.sample().await;
+        worker
+            .call(|bus| bus.set_temperature(FIRST, 18.0))
+            .await
+            .unwrap();
+        reader.sample().await;
+        reader.reset_statistics(Some(0)).unwrap();
+        let reset = reading(&reader, 0);
+        assert_eq!(reset.window.count, 0);
+        assert!(reset.window.start > 0);
+        reader.sample().await;
+        // The window starts over, the statistics since boot go on
+        let first = reading(&reader, 0);
+        assert_eq!((first.window.count, first.window.max), (1, 18.0));
+        assert_eq!((first.statistics.count, first.statistics.max), (3, 21.5));
+        assert_eq!(reading(&reader, 1).window.count, 3);
+        assert!(matches!(
+            reader.reset_statistics(Some(2)),
+            Err(Error::FreeSlot { slot: 2 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn rate_of_change() {
+        let simulator = Simulator::default().with_device(FIRST, 21.5);
+        let (mut reader, worker, _) = reader(simulator);
+        reader.rescan().await;
+        reader
+            .set_limits(0, Limits::new(1.0, Duration::ZERO, 0.0).unwrap())
+            .unwrap();
+        reader.sample().await;
+        worker
+            .call(|bus| bus.set_temperature(FIRST, 30.0))
+            .await
+            .unwrap();
+        reader.sample().await;
+        // A flagged jump leaves the statistics and the filter alone
+        let spike = reading(&reader, 0);
+        assert_eq!(spike.status, Status::RateOfChange);
+        assert_eq!(spike.temperature, 30.0);
+        assert_eq!((spike.statistics.min, spike.statistics.max), (21.5, 21.5));
+        assert_eq!((spike.window.min, spike.window.max), (21.5, 21.5));
+        assert_eq!(spike.filtered, 21.5);
+        // But is recorded
+        let recorded = reader.history(0, 0..u32::MAX).unwrap();
+        assert_eq!(recorded.last().unwrap().status, Status::RateOfChange);
+    }
+
+    #[tokio::test]
+    async fn power_on_reset() {
+        let ds18s20 = 0x6500_0000_3C1F_7D10;
+        let simulator = Simulator::default()
+            .with_device(FIRST, 85.0)
+            .with_device(ds18s20, 84.5);
+        let (mut reader, worker, _) = reader(simulator);
+        reader.rescan().await;
+        reader.sample().await;
+        // A genuine DS18B20 tells a conversion apart by byte 6
+        let first = reading(&reader, 0);
+        assert_eq!(first.status, Status::Ok);
+        assert_eq!(first.temperature, 85.0);
+        assert_eq!(reading(&reader, 1).status, Status::Ok);
+        // The DS18S20 counts down to the reset value, reached from a valid
+        // sample
+        worker
+            .call(move |bus| bus.set_temperature(ds18s20, 85.0))
+            .await
+            .unwrap();
+        reader.sample().await;
+        let second = reading(&reader, 1);
+        assert_eq!(second.status, Status::Ok);
+        assert_eq!(second.temperature, 85.0);
+        assert_eq!(second.counters.power_on_reset, 0);
+    }
+
+    #[tokio::test]
+    async fn brown_out() {
+        let simulator = Simulator::default().with_device(FIRST, 21.5);
+        let (mut reader, worker, _) = reader(simulator);
+        reader.rescan().await;
+        worker
+            .call(|bus| bus.set_brown_out(FIRST, true))
+            .await
+            .unwrap();
+        // Never accepted, not even at boot
+        for count in 1..=2 {
+            reader.sample().await;
+            let reset = reading(&reader, 0);
+            assert_eq!(reset.status, Status::PowerOnReset);
+            assert!(reset.temperature.is_nan());
+            assert_eq!(reset.counters.power_on_reset, count);
+        }
+        worker
+            .call(|bus| bus.set_brown_out(FIRST, false))
+            .await
+            .unwrap();
+        reader.sample().await;
+        assert_eq!(reading(&reader, 0).temperature, 21.5);
+        // Repeated brown-outs keep the last temperature
+        worker
+            .call(|bus| bus.set_brown_out(FIRST, true))
+            .await
+            .unwrap();
+        for count in 3..=5 {
+            reader.sample().await;
+            let reset = reading(&reader, 0);
+            assert_eq!(reset.status, Status::PowerOnReset);
+            assert_eq!(reset.temperature, 21.5);
+            assert_eq!(reset.counters.power_on_reset, count);
+        }
+    }
+
+    #[tokio::test]
+    async fn eeprom() {
+        let simulator = Simulator::default().with_device(FIRST, 21.5);
+        let (mut reader, _, _) = reader(simulator);
+        reader.rescan().await;
+        let stored = Configuration::new(9, 40, -5).unwrap();
+        reader.configure(0, stored).await.unwrap();
+        assert_eq!(reader.copy_scratchpad(Some(0)).await.unwrap(), 1);
+        // Already stored
+        assert_eq!(reader.copy_scratchpad(None).await.unwrap(), 0);
+        reader.configure(0, Configuration::default()).await.unwrap();
+        reader.sample().await;
+        reader.recall_eeprom(Some(0)).await.unwrap();
+        assert_eq!(reader.settings.configuration(FIRST), stored);
+        assert_eq!(reading(&reader, 0).configuration, stored);
+        assert_eq!(
+            reader.lines[0].conversion.time,
+            Duration::from_micros(93_750)
+        );
+    }
+
+    #[tokio::test]
+    async fn short_circuit() {
+        let simulator = Simulator::default().with_device(FIRST, 21.5);
+        let (mut reader, worker, _) = reader(simulator);
+        reader.rescan().await;
+        reader.sample().await;
+        worker
+            .call(|bus| bus.set_short_circuit(true))
+            .await
+            .unwrap();
+        reader.sample().await;
+        let shorted = reading(&reader, 0);
+        assert_eq!(shorted.status, Status::Stale);
+        assert_eq!(shorted.temperature, 21.5);
+    }
+}
@@ -0,0 +1,242 @@
+use super::family::Family;
+
+/// Reserved bits of the configuration register, always read as ones
+const CONFIGURATION_RESERVED: u8 = 0b0001_1111;
+
+/// Conversion resolution
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum Resolution {
+    Nine,
+    Ten,
+    Eleven,
+    #[default]
+    Twelve,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ConfigurationRegister {
+    pub(crate) resolution: Resolution,
+}
+
+/// Decoded scratchpad
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Scratchpad {
+    /// Temperature, °C
+    pub(crate) temperature: f32,
+    /// TH alarm trigger, °C
+    pub(crate) alarm_high_trigger_register: i8,
+    /// TL alarm trigger, °C
+    pub(crate) alarm_low_trigger_register: i8,
+    pub(crate) configuration_register: ConfigurationRegister,
+    /// Byte 6, the count remaining (reserved on the DS18B20 and DS1822)
+    pub(crate) count_remain: u8,
+}
+
+/// Dallas/Maxim CRC-8 (polynomial x⁸ + x⁵ + x⁴ + 1)
+pub(crate) fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x8C
+            } else {
+                crc >> 1
+            };
+        }
+        crc
+    })
+}
+
+/// Whether the bytes carry a valid CRC
+///
+/// A shorted line reads all zeros, which passes the CRC on its own but can
+/// never be a scratchpad.
+pub(crate) fn verify(bytes: &[u8; 9]) -> bool {
+    crc8(&bytes[..8]) == bytes[8] && bytes.iter().any(|&byte| byte != 0)
+}
+
+/// Decodes the scratchpad of the family, `None` if the device reports a
+/// fault
+pub(crate) fn decode(family: Family, bytes: &[u8; 9]) -> Option<Scratchpad> {
+    let scratchpad = Scratchpad {
+        alarm_high_trigger_register: bytes[2] as _,
+        alarm_low_trigger_register: bytes[3] as _,
+        count_remain: bytes[6],
+        ..Default::default()
+    };
+    match family {
+        Family::Ds18s20 => {
+            // Half degrees, extended by the count remaining in the last degree
+            let temperature = i16::from_le_bytes([bytes[0], bytes[1]]);
+            let (count_remain, count_per_c) = (bytes[6], bytes[7]);
+            let temperature = if count_per_c == 0 {
+                temperature as f32 / 2.0
+            } else {
+                (temperature >> 1) as f32 - 0.25
+                    + (count_per_c as f32 - count_remain as f32) / count_per_c as f32
+            };
+            Some(Scratchpad {
+                temperature,
+                configuration_register: ConfigurationRegister {
+                    resolution: Resolution::Nine,
+                },
+                ..scratchpad
+            })
+        }
+        Family::Ds1822 | Family::Ds18b20 => {
+            let resolution = resolution(9 + (bytes[4] >> 5 & 0b11)).unwrap_or(Resolution::Twelve);
+            // Low bits are undefined below 12-bit resolution
+            let mask = !((1 << (12 - bits(resolution))) - 1);
+            let temperature = i16::from_le_bytes([bytes[0], bytes[1]]) & mask;
+            Some(Scratchpad {
+                temperature: temperature as f32 / 16.0,
+                configuration_register: ConfigurationRegister { resolution },
+                ..scratchpad
+            })
+        }
+        Family::Max31850 => {
+            // Quarter degrees in the upper 14 bits, the fault flag in bit 0
+            let temperature = i16::from_le_bytes([bytes[0], bytes[1]]);
+            if temperature & 1 != 0 {
+                return None;
+            }
+            Some(Scratchpad {
+                temperature: (temperature >> 2) as f32 / 4.0,
+                alarm_high_trigger_register: 0,
+                alarm_low_trigger_register: 0,
+                ..scratchpad
+            })
+        }
+    }
+}
+
+/// Encodes the writable part: TH, TL and configuration registers, the
+/// DS18S20 takes TH and TL only
+pub(crate) fn encode(scratchpad: &Scratchpad) -> [u8; 3] {
+    [
+        scratchpad.alarm_high_trigger_register as _,
+        scratchpad.alarm_low_trigger_register as _,
+        (bits(scratchpad.configuration_register.resolution) - 9) << 5 | CONFIGURATION_RESERVED,
+    ]
+}
+
+pub(crate) fn bits(resolution: Resolution) -> u8 {
+    match resolution {
+        Resolution::Nine => 9,
+        Resolution::Ten => 10,
+        Resolution::Eleven => 11,
+        Resolution::Twelve => 12,
+    }
+}
+
+pub(crate) fn resolution(bits: u8) -> Option<Resolution> {
+    match bits {
+        9 => Some(Resolution::Nine),
+        10 => Some(Resolution::Ten),
+        11 => Some(Resolution::Eleven),
+        12 => Some(Resolution::Twelve),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scratchpad bytes with the temperature register, configuration and
+    /// count registers
+    fn bytes(temperature: u16, configuration: u8, count_remain: u8, count_per_c: u8) -> [u8; 9] {
+        let [low, high] = temperature.to_le_bytes();
+        let mut bytes = [
+            low,
+            high,
+            0x4B,
+            0x46,
+            configuration,
+            0xFF,
+            count_remain,
+            count_per_c,
+            0,
+        ];
+        bytes[8] = crc8(&bytes[..8]);
+        bytes
+    }
+
+    fn temperature(family: Family, bytes: &[u8; 9]) -> Option<f32> {
+        decode(family, bytes).map(|scratchpad| scratchpad.temperature)
+    }
+
+    #[test]
+    fn verify() {
+        assert!(super::verify(&bytes(0x0191, 0x7F, 0x0F, 0x10)));
+        let mut corrupted = bytes(0x0191, 0x7F, 0x0F, 0x10);
+        corrupted[0] ^= 1;
+        assert!(!super::verify(&corrupted));
+        // A shorted line
+        assert!(!super::verify(&[0; 9]));
+    }
+
+    #[test]
+    fn ds18s20() {
+        // Extended resolution from the count registers
+        assert_eq!(
+            temperature(Family::Ds18s20, &bytes(0x0032, 0xFF, 0x0C, 0x10)),
+            Some(25.0)
+        );
+        assert_eq!(
+            temperature(Family::Ds18s20, &bytes(0xFFFF, 0xFF, 0x04, 0x10)),
+            Some(-0.5)
+        );
+        // Half degrees without them
+        assert_eq!(
+            temperature(Family::Ds18s20, &bytes(0x0033, 0xFF, 0x0C, 0x00)),
+            Some(25.5)
+        );
+    }
+
+    #[test]
+    fn ds18b20() {
+        let scratchpad = decode(Family::Ds18b20, &bytes(0xFF5E, 0x7F, 0x0C, 0x10)).unwrap();
+        assert_eq!(scratchpad.temperature, -10.125);
+        assert_eq!(scratchpad.alarm_high_trigger_register, 75);
+        assert_eq!(scratchpad.alarm_low_trigger_register, 70);
+        assert_eq!(scratchpad.count_remain, 0x0C);
+        // Undefined low bits are masked below 12 bits
+        let scratchpad = decode(Family::Ds1822, &bytes(0x0191, 0x1F, 0x0C, 0x10)).unwrap();
+        assert_eq!(scratchpad.temperature, 25.0);
+        assert_eq!(
+            scratchpad.configuration_register.resolution,
+            Resolution::Nine
+        );
+    }
+
+    #[test]
+    fn max31850() {
+        assert_eq!(
+            temperature(Family::Max31850, &bytes(0x0190, 0xF0, 0xFF, 0xFF)),
+            Some(25.0)
+        );
+        assert_eq!(
+            temperature(Family::Max31850, &bytes(0xFF38, 0xF0, 0xFF, 0xFF)),
+            Some(-12.5)
+        );
+        // Open or shorted thermocouple
+        assert_eq!(
+            temperature(Family::Max31850, &bytes(0x0191, 0xF0, 0xFF, 0xFF)),
+            None
+        );
+    }
+
+    #[test]
+    fn encode() {
+        let scratchpad = Scratchpad {
+            alarm_high_trigger_register: 40,
+            alarm_low_trigger_register: -5,
+            configuration_register: ConfigurationRegister {
+                resolution: Resolution::Ten,
+            },
+            ..Default::default()
+        };
+        assert_eq!(super::encode(&scratchpad), [40, 0xFB, 0x3F]);
+    }
+}
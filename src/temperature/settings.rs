@@ -0,0 +1,283 @@
+use super::{
+    Error, Result,
+    calibration::Calibration,
+    detection::Limits,
+    family::Family,
+    filter::Filter,
+    scratchpad::{ConfigurationRegister, Resolution, Scratchpad, bits, resolution},
+    slots::{SLOTS, Slots},
+    store::Store,
+};
+use log::warn;
+use std::{collections::BTreeMap, fmt::Debug, time::Duration};
+
+const CONFIGURATIONS: &str = "configurations";
+const CALIBRATIONS: &str = "calibrations";
+const FILTERS: &str = "filters";
+const LIMITS: &str = "limits";
+const INTERVAL: &str = "interval";
+const HISTORY: &str = "history";
+const RECORDS: usize = SLOTS;
+
+/// Sensor configuration
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Configuration {
+    pub(crate) resolution: Resolution,
+    pub(crate) alarm_high_trigger: i8,
+    pub(crate) alarm_low_trigger: i8,
+}
+
+impl Configuration {
+    /// Configuration from the resolution in bits and the alarm triggers, °C,
+    /// `None` if TL is above TH
+    pub(crate) fn new(bits: u8, alarm_high_trigger: i8, alarm_low_trigger: i8) -> Option<Self> {
+        (alarm_low_trigger <= alarm_high_trigger).then_some(Self {
+            resolution: resolution(bits)?,
+            alarm_high_trigger,
+            alarm_low_trigger,
+        })
+    }
+
+    /// Configuration the scratchpad holds, `None` if TL is above TH
+    pub(crate) fn from_scratchpad(scratchpad: &Scratchpad) -> Option<Self> {
+        Self::new(
+            bits(scratchpad.configuration_register.resolution),
+            scratchpad.alarm_high_trigger_register,
+            scratchpad.alarm_low_trigger_register,
+        )
+    }
+
+    /// Resolution in bits
+    pub(crate) fn bits(&self) -> u8 {
+        bits(self.resolution)
+    }
+
+    /// Maximum conversion time of a sensor of the family
+    pub(crate) fn conversion_time(&self, family: Family) -> Duration {
+        family.conversion_time(self.resolution)
+    }
+
+    /// Whether the sensor already holds this configuration, as far as its
+    /// family supports it
+    pub(crate) fn matches(&self, family: Family, scratchpad: &Scratchpad) -> bool {
+        (!family.has_alarm()
+            || scratchpad.alarm_high_trigger_register == self.alarm_high_trigger
+                && scratchpad.alarm_low_trigger_register == self.alarm_low_trigger)
+            && (!family.has_resolution()
+                || scratchpad.configuration_register.resolution == self.resolution)
+    }
+
+    pub(crate) fn scratchpad(&self) -> Scratchpad {
+        Scratchpad {
+            alarm_high_trigger_register: self.alarm_high_trigger,
+            alarm_low_trigger_register: self.alarm_low_trigger,
+            configuration_register: ConfigurationRegister {
+                resolution: self.resolution,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn from_bytes(bytes: &[u8; 3]) -> Option<Self> {
+        Self::new(bytes[0], bytes[1] as _, bytes[2] as _)
+    }
+
+    fn to_bytes(self) -> [u8; 3] {
+        [
+            self.bits(),
+            self.alarm_high_trigger as _,
+            self.alarm_low_trigger as _,
+        ]
+    }
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            resolution: Resolution::Twelve,
+            alarm_high_trigger: 30,
+            alarm_low_trigger: 10,
+        }
+    }
+}
+
+/// Persistent sensor settings
+///
+/// Records are keyed by ROM address, so they follow the sensor whatever slot
+/// it takes. Changes last until the next restart unless saved, only the
+/// records of sensors holding a slot are saved.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Settings {
+    configurations: BTreeMap<u64, Configuration>,
+    calibrations: BTreeMap<u64, Calibration>,
+    filters: BTreeMap<u64, Filter>,
+    limits: BTreeMap<u64, Limits>,
+    /// Sample interval, the reader options unless set
+    pub(crate) interval: Option<Duration>,
+    /// Samples kept in the history of every sensor, the reader options unless
+    /// set
+    pub(crate) history: Option<usize>,
+}
+
+impl Settings {
+    pub(crate) fn load(nvs: &impl Store) -> Result<Self> {
+        let mut interval = [0; 4];
+        let mut history = [0; 4];
+        Ok(Self {
+            configurations: load(nvs, CONFIGURATIONS, Configuration::from_bytes)?,
+            calibrations: load(nvs, CALIBRATIONS, Calibration::from_bytes)?,
+            filters: load(nvs, FILTERS, Filter::from_bytes)?,
+            limits: load(nvs, LIMITS, Limits::from_bytes)?,
+            interval: nvs
+                .get_blob(INTERVAL, &mut interval)?
+                .and_then(|bytes| Some(u32::from_le_bytes(bytes.try_into().ok()?)))
+                .map(|milliseconds| Duration::from_millis(milliseconds as _)),
+            history: nvs
+                .get_blob(HISTORY, &mut history)?
+                .and_then(|bytes| Some(u32::from_le_bytes(bytes.try_into().ok()?) as _)),
+        })
+    }
+
+    pub(crate) fn save(&self, nvs: &mut impl Store, slots: &Slots) -> Result<()> {
+        save(
+            nvs,
+            CONFIGURATIONS,
+            &self.configurations,
+            slots,
+            Configuration::to_bytes,
+        )?;
+        save(
+            nvs,
+            CALIBRATIONS,
+            &self.calibrations,
+            slots,
+            Calibration::to_bytes,
+        )?;
+        save(nvs, FILTERS, &self.filters, slots, Filter::to_bytes)?;
+        save(nvs, LIMITS, &self.limits, slots, Limits::to_bytes)?;
+        if let Some(interval) = self.interval {
+            nvs.set_blob(INTERVAL, &(interval.as_millis() as u32).to_le_bytes())?;
+        }
+        if let Some(history) = self.history {
+            nvs.set_blob(HISTORY, &(history as u32).to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Configuration of the sensor, defaults for unknown sensors
+    pub(crate) fn configuration(&self, address: u64) -> Configuration {
+        self.configurations
+            .get(&address)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Calibration of the sensor, identity for uncalibrated sensors
+    pub(crate) fn calibration(&self, address: u64) -> Calibration {
+        self.calibrations.get(&address).copied().unwrap_or_default()
+    }
+
+    /// Filter of the sensor, unfiltered by default
+    pub(crate) fn filter(&self, address: u64) -> Filter {
+        self.filters.get(&address).copied().unwrap_or_default()
+    }
+
+    /// Fault detection limits of the sensor, detection is off by default
+    pub(crate) fn limits(&self, address: u64) -> Limits {
+        self.limits.get(&address).copied().unwrap_or_default()
+    }
+
+    /// Changes the configuration until the next restart
+    pub(crate) fn set_configuration(&mut self, address: u64, configuration: Configuration) {
+        self.configurations.insert(address, configuration);
+    }
+
+    /// Changes the calibration until the next restart
+    pub(crate) fn set_calibration(&mut self, address: u64, calibration: Calibration) {
+        self.calibrations.insert(address, calibration);
+    }
+
+    /// Changes the filter until the next restart
+    pub(crate) fn set_filter(&mut self, address: u64, filter: Filter) {
+        self.filters.insert(address, filter);
+    }
+
+    /// Changes the fault detection limits until the next restart
+    pub(crate) fn set_limits(&mut self, address: u64, limits: Limits) {
+        self.limits.insert(address, limits);
+    }
+}
+
+/// Loads `(address, value)` records
+fn load<const N: usize, T: Debug>(
+    nvs: &impl Store,
+    key: &str,
+    from_bytes: impl Fn(&[u8; N]) -> Option<T>,
+) -> Result<BTreeMap<u64, T>> {
+    let mut buffer = vec![0; (8 + N) * RECORDS];
+    let Some(bytes) = nvs.get_blob(key, &mut buffer)? else {
+        return Ok(BTreeMap::new());
+    };
+    let mut records = BTreeMap::new();
+    for record in bytes.chunks_exact(8 + N) {
+        let (address, value) = record.split_at(8);
+        let address = u64::from_le_bytes(address.try_into().unwrap());
+        match from_bytes(value.try_into().unwrap()) {
+            Some(value) => {
+                records.insert(address, value);
+            }
+            None => warn!("Invalid {key} record {address:x?}: {value:x?}"),
+        }
+    }
+    Ok(records)
+}
+
+/// Saves the `(address, value)` records of the sensors holding a slot, fails
+/// if they do not fit the load buffer
+fn save<const N: usize, T: Copy>(
+    nvs: &mut impl Store,
+    key: &'static str,
+    records: &BTreeMap<u64, T>,
+    slots: &Slots,
+    to_bytes: impl Fn(T) -> [u8; N],
+) -> Result<()> {
+    let records: Vec<_> = records
+        .iter()
+        .filter(|&(&address, _)| slots.slot(address).is_some())
+        .collect();
+    if records.len() > RECORDS {
+        return Err(Error::TooManyRecords {
+            key,
+            count: records.len(),
+            max: RECORDS,
+        });
+    }
+    let bytes: Vec<_> = records
+        .into_iter()
+        .flat_map(|(address, value)| [&address.to_le_bytes()[..], &to_bytes(*value)].concat())
+        .collect();
+    nvs.set_blob(key, &bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::store::Memory, *};
+
+    #[test]
+    fn save() {
+        let (present, gone) = (0x1A00_0000_4F3B_6C28, 0x6E00_0000_52A1_9D28);
+        let mut slots = Slots::default();
+        slots.assign(&[present]);
+        let mut settings = Settings::default();
+        let configuration = Configuration::new(9, 40, -5).unwrap();
+        settings.set_configuration(present, configuration);
+        settings.set_configuration(gone, configuration);
+        let mut nvs = Memory::default();
+        settings.save(&mut nvs, &slots).unwrap();
+        // Records of sensors without a slot are dropped
+        let saved = Settings::load(&nvs).unwrap();
+        assert_eq!(saved.configuration(present), configuration);
+        assert_eq!(saved.configuration(gone), Configuration::default());
+    }
+}
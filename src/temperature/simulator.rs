@@ -0,0 +1,336 @@
+use super::{
+    Power,
+    bus::{Bus, Error, Result, Rom},
+    family::Family,
+    scratchpad::crc8,
+};
+use std::collections::BTreeMap;
+
+/// DS18B20 and DS1822 scratchpad after power-up: 85 °C, TH 75 °C, TL 70 °C,
+/// 12 bits
+const POWER_ON: [u8; 8] = [0x50, 0x05, 0x4B, 0x46, 0x7F, 0xFF, 0x0C, 0x10];
+/// DS18S20 scratchpad after power-up: 85 °C, TH 75 °C, TL 70 °C
+const DS18S20_POWER_ON: [u8; 8] = [0xAA, 0x00, 0x4B, 0x46, 0xFF, 0xFF, 0x0C, 0x10];
+/// MAX31850 scratchpad after power-up: 0 °C, cold junction 0 °C, address 0
+const MAX31850_POWER_ON: [u8; 8] = [0x00, 0x00, 0x00, 0x00, 0xF0, 0xFF, 0xFF, 0xFF];
+/// Cold junction temperature of the simulated MAX31850, °C
+const COLD_JUNCTION: f32 = 25.0;
+
+/// Simulated 1-Wire temperature bus
+///
+/// Scriptable in-memory replacement of the real driver: devices can be
+/// added, removed, heated, browned out and made to fail CRC checks, the line
+/// shorted and transactions made to time out or searches to conflict, at any
+/// time.
+#[derive(Clone, Debug, Default)]
+pub struct Simulator {
+    devices: BTreeMap<u64, Device>,
+    /// Line stuck low
+    shorted: bool,
+    /// Transactions left to time out
+    timeouts: usize,
+    /// Searches left to conflict
+    conflicts: usize,
+}
+
+impl Simulator {
+    /// Connects a device with the given ROM and temperature, the family code
+    /// selects the scratchpad layout (DS18B20 if unknown)
+    pub(crate) fn insert(&mut self, address: u64, temperature: f32) {
+        let family = Family::of(address).unwrap_or(Family::Ds18b20);
+        self.devices.insert(
+            address,
+            Device {
+                temperature,
+                ..Device::new(family)
+            },
+        );
+    }
+
+    /// Initialization followed by Match ROM, `None` if nobody answers
+    fn select(&mut self, address: u64) -> Result<Option<&mut Device>> {
+        self.initialization()?;
+        Ok(self.devices.get_mut(&address))
+    }
+
+    /// Applies `f` to the addressed devices
+    fn each(&mut self, rom: Rom, f: impl Fn(&mut Device)) -> Result<()> {
+        match rom {
+            Rom::Match(address) => {
+                if let Some(device) = self.select(address)? {
+                    f(device);
+                }
+            }
+            Rom::Skip => {
+                self.initialization()?;
+                self.devices.values_mut().for_each(f);
+            }
+        }
+        Ok(())
+    }
+
+    fn initialization(&mut self) -> Result<()> {
+        if self.shorted {
+            return Err(Error::ShortCircuit);
+        }
+        if self.timeouts > 0 {
+            self.timeouts -= 1;
+            return Err(Error::Timeout);
+        }
+        if self.devices.is_empty() {
+            return Err(Error::NoPresence);
+        }
+        Ok(())
+    }
+
+    /// Search ROM colliding on a bit no device answers consistently
+    fn conflict(&mut self) -> Result<()> {
+        if self.conflicts > 0 {
+            self.conflicts -= 1;
+            return Err(Error::Conflict);
+        }
+        Ok(())
+    }
+}
+
+/// Scripting API of the tests
+#[cfg(test)]
+impl Simulator {
+    /// Connects a device with the given ROM and temperature
+    pub(crate) fn with_device(mut self, address: u64, temperature: f32) -> Self {
+        self.insert(address, temperature);
+        self
+    }
+
+    /// Disconnects the device
+    pub(crate) fn remove(&mut self, address: u64) -> bool {
+        self.devices.remove(&address).is_some()
+    }
+
+    /// Sets the temperature the device will measure on the next conversion
+    pub(crate) fn set_temperature(&mut self, address: u64, temperature: f32) {
+        if let Some(device) = self.devices.get_mut(&address) {
+            device.temperature = temperature;
+        }
+    }
+
+    /// Switches the device between parasitic and external power
+    pub(crate) fn set_power(&mut self, address: u64, power: Power) {
+        if let Some(device) = self.devices.get_mut(&address) {
+            device.power = power;
+        }
+    }
+
+    /// Makes every conversion of the device brown it out, resetting the
+    /// scratchpad to its power-on value instead of converting
+    pub(crate) fn set_brown_out(&mut self, address: u64, brown_out: bool) {
+        if let Some(device) = self.devices.get_mut(&address) {
+            device.brown_out = brown_out;
+        }
+    }
+
+    /// Corrupts the CRC of the next `count` scratchpad reads of the device
+    pub(crate) fn inject_crc_faults(&mut self, address: u64, count: usize) {
+        if let Some(device) = self.devices.get_mut(&address) {
+            device.crc_faults = count;
+        }
+    }
+
+    /// Shorts the line to ground or releases it
+    pub(crate) fn set_short_circuit(&mut self, shorted: bool) {
+        self.shorted = shorted;
+    }
+
+    /// Times out the next `count` transactions
+    pub(crate) fn inject_timeouts(&mut self, count: usize) {
+        self.timeouts = count;
+    }
+
+    /// Makes the next `count` searches, alarm searches included, conflict
+    pub(crate) fn inject_conflicts(&mut self, count: usize) {
+        self.conflicts = count;
+    }
+}
+
+impl Bus for Simulator {
+    fn search(&mut self) -> Result<Vec<u64>> {
+        self.initialization()?;
+        self.conflict()?;
+        Ok(self.devices.keys().copied().collect())
+    }
+
+    fn convert_temperature(&mut self, rom: Rom) -> Result<()> {
+        self.each(rom, Device::convert)
+    }
+
+    /// Conversions complete instantly
+    fn is_converted(&mut self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn read_power_supply(&mut self, rom: Rom) -> Result<Power> {
+        let parasitic = match rom {
+            Rom::Match(address) => self
+                .select(address)?
+                .is_some_and(|device| device.power == Power::Parasitic),
+            Rom::Skip => {
+                self.initialization()?;
+                self.devices
+                    .values()
+                    .any(|device| device.power == Power::Parasitic)
+            }
+        };
+        Ok(if parasitic {
+            Power::Parasitic
+        } else {
+            Power::External
+        })
+    }
+
+    fn copy_scratchpad(&mut self, rom: Rom) -> Result<()> {
+        self.each(rom, Device::copy)
+    }
+
+    fn recall_eeprom(&mut self, rom: Rom) -> Result<()> {
+        self.each(rom, Device::recall)
+    }
+
+    fn alarm_search(&mut self, address: u64) -> Result<bool> {
+        self.initialization()?;
+        self.conflict()?;
+        Ok(self
+            .devices
+            .get(&address)
+            .is_some_and(|device| device.alarm))
+    }
+
+    fn read_raw(&mut self, address: u64) -> Result<[u8; 9]> {
+        // An absent device does not drive the line, the master reads all ones
+        let Some(device) = self.select(address)? else {
+            return Ok([0xFF; 9]);
+        };
+        let mut bytes = [0; 9];
+        bytes[..8].copy_from_slice(&device.memory);
+        bytes[8] = crc8(&device.memory);
+        if device.crc_faults > 0 {
+            device.crc_faults -= 1;
+            bytes[8] = !bytes[8];
+        }
+        Ok(bytes)
+    }
+
+    fn write_raw(&mut self, rom: Rom, bytes: &[u8]) -> Result<()> {
+        match rom {
+            Rom::Match(address) => {
+                if let Some(device) = self.select(address)? {
+                    device.write(bytes);
+                }
+            }
+            Rom::Skip => {
+                self.initialization()?;
+                self.devices
+                    .values_mut()
+                    .for_each(|device| device.write(bytes));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Simulated device
+#[derive(Clone, Debug)]
+struct Device {
+    family: Family,
+    power: Power,
+    temperature: f32,
+    crc_faults: usize,
+    /// Resets on conversion
+    brown_out: bool,
+    /// Alarm flag, set by a conversion at or beyond TH or TL
+    alarm: bool,
+    memory: [u8; 8],
+    /// TH, TL and configuration registers
+    eeprom: [u8; 3],
+}
+
+impl Device {
+    fn new(family: Family) -> Self {
+        let memory = power_on(family);
+        Self {
+            family,
+            power: Power::External,
+            temperature: 0.0,
+            crc_faults: 0,
+            brown_out: false,
+            alarm: false,
+            memory,
+            eeprom: [memory[2], memory[3], memory[4]],
+        }
+    }
+
+    fn copy(&mut self) {
+        if self.family.has_eeprom() {
+            self.eeprom.copy_from_slice(&self.memory[2..5]);
+        }
+    }
+
+    fn recall(&mut self) {
+        if self.family.has_eeprom() {
+            self.memory[2..5].copy_from_slice(&self.eeprom);
+        }
+    }
+
+    /// Whole degrees are compared against TH and TL
+    fn convert(&mut self) {
+        if self.brown_out {
+            // The registers are reloaded from the EEPROM on power-up
+            self.memory = power_on(self.family);
+            self.recall();
+            self.alarm = false;
+            return;
+        }
+        let degrees = self.temperature.floor() as i8;
+        self.alarm = self.family.has_alarm()
+            && (degrees >= self.memory[2] as i8 || degrees <= self.memory[3] as i8);
+        match self.family {
+            Family::Ds18s20 => {
+                // Whole degrees (offset by the 0.25 °C the decoding takes
+                // off) plus the sixteenths counted down in the remainder
+                let degrees = (self.temperature + 0.25).floor();
+                let sixteenths = ((self.temperature + 0.25 - degrees) * 16.0).round() as u8;
+                let temperature = degrees as i16 * 2;
+                self.memory[..2].copy_from_slice(&temperature.to_le_bytes());
+                self.memory[6] = 16 - sixteenths;
+                self.memory[7] = 16;
+            }
+            Family::Ds1822 | Family::Ds18b20 => {
+                let temperature = (self.temperature * 16.0).round() as i16;
+                self.memory[..2].copy_from_slice(&temperature.to_le_bytes());
+                // As a genuine DS18B20, where byte 6 follows the low bits
+                self.memory[6] = 0x10 - (temperature & 0x0F) as u8;
+            }
+            Family::Max31850 => {
+                let temperature = ((self.temperature * 4.0).round() as i16) << 2;
+                let cold_junction = ((COLD_JUNCTION * 16.0).round() as i16) << 4;
+                self.memory[..2].copy_from_slice(&temperature.to_le_bytes());
+                self.memory[2..4].copy_from_slice(&cold_junction.to_le_bytes());
+            }
+        }
+    }
+
+    /// The MAX31850 has no writable registers
+    fn write(&mut self, bytes: &[u8]) {
+        if self.family != Family::Max31850 {
+            let length = bytes.len().min(3);
+            self.memory[2..2 + length].copy_from_slice(&bytes[..length]);
+        }
+    }
+}
+
+fn power_on(family: Family) -> [u8; 8] {
+    match family {
+        Family::Ds18s20 => DS18S20_POWER_ON,
+        Family::Ds1822 | Family::Ds18b20 => POWER_ON,
+        Family::Max31850 => MAX31850_POWER_ON,
+    }
+}
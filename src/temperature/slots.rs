@@ -0,0 +1,85 @@
+use super::{Result, store::Store};
+use log::{info, warn};
+
+const KEY: &str = "slots";
+const FREE: u64 = 0;
+
+/// Maximum number of slots
+pub(crate) const SLOTS: usize = 32;
+
+/// Slot table
+///
+/// Maps ROM addresses to stable slots, a sensor keeps its slot (and thus its
+/// Modbus registers) when other sensors are added, replaced or go missing.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Slots(Vec<Option<u64>>);
+
+impl Slots {
+    pub(crate) fn load(nvs: &impl Store) -> Result<Self> {
+        let mut buffer = [0; 8 * SLOTS];
+        let Some(bytes) = nvs.get_blob(KEY, &mut buffer)? else {
+            return Ok(Self::default());
+        };
+        let mut slots: Vec<_> = bytes
+            .as_chunks::<8>()
+            .0
+            .iter()
+            .map(|&bytes| Some(u64::from_le_bytes(bytes)).filter(|&address| address != FREE))
+            .collect();
+        while slots.pop_if(|slot| slot.is_none()).is_some() {}
+        Ok(Self(slots))
+    }
+
+    pub(crate) fn save(&self, nvs: &mut impl Store) -> Result<()> {
+        let bytes: Vec<_> = self
+            .0
+            .iter()
+            .flat_map(|slot| slot.unwrap_or(FREE).to_le_bytes())
+            .collect();
+        nvs.set_blob(KEY, &bytes)?;
+        Ok(())
+    }
+
+    /// Assigns free slots to unknown addresses, returns whether the table
+    /// changed
+    pub(crate) fn assign(&mut self, addresses: &[u64]) -> bool {
+        let mut changed = false;
+        for &address in addresses {
+            if self.slot(address).is_some() {
+                continue;
+            }
+            let slot = match self.0.iter().position(Option::is_none) {
+                Some(slot) => slot,
+                None if self.0.len() < SLOTS => {
+                    self.0.push(None);
+                    self.0.len() - 1
+                }
+                None => {
+                    warn!("No free slot for {address:x?}");
+                    continue;
+                }
+            };
+            info!("Assign slot {slot} to {address:x?}");
+            self.0[slot] = Some(address);
+            changed = true;
+        }
+        changed
+    }
+
+    /// Frees the slot, returns the address it held
+    pub(crate) fn release(&mut self, slot: usize) -> Option<u64> {
+        let address = self.0.get_mut(slot)?.take()?;
+        info!("Release slot {slot} of {address:x?}");
+        Some(address)
+    }
+
+    /// Slot of the address
+    pub(crate) fn slot(&self, address: u64) -> Option<usize> {
+        self.0.iter().position(|&slot| slot == Some(address))
+    }
+
+    /// Addresses by slot, `None` for free slots
+    pub(crate) fn iter(&self) -> impl Iterator<Item = Option<u64>> + '_ {
+        self.0.iter().copied()
+    }
+}
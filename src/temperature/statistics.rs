@@ -0,0 +1,78 @@
+/// Running statistics of valid temperatures
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Statistics {
+    /// Start of the window, seconds since the Unix epoch
+    pub(crate) start: u32,
+    pub(crate) count: u32,
+    pub(crate) min: f32,
+    pub(crate) max: f32,
+    pub(crate) mean: f32,
+    /// Time of the minimum, seconds since the Unix epoch
+    pub(crate) min_timestamp: u32,
+    /// Time of the maximum, seconds since the Unix epoch
+    pub(crate) max_timestamp: u32,
+}
+
+impl Statistics {
+    pub(crate) fn new(start: u32) -> Self {
+        Self {
+            start,
+            count: 0,
+            min: f32::NAN,
+            max: f32::NAN,
+            mean: f32::NAN,
+            min_timestamp: 0,
+            max_timestamp: 0,
+        }
+    }
+
+    pub(crate) fn update(&mut self, temperature: f32, timestamp: u32) {
+        self.count += 1;
+        if self.count == 1 || temperature < self.min {
+            self.min = temperature;
+            self.min_timestamp = timestamp;
+        }
+        if self.count == 1 || temperature > self.max {
+            self.max = temperature;
+            self.max_timestamp = timestamp;
+        }
+        self.mean = if self.count == 1 {
+            temperature
+        } else {
+            // Incremental, a running sum loses precision over long runs
+            self.mean + (temperature - self.mean) / self.count as f32
+        };
+    }
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update() {
+        let mut statistics = Statistics::new(100);
+        assert!(statistics.mean.is_nan());
+        for (temperature, timestamp) in [(21.0, 101), (19.0, 102), (26.0, 103), (22.0, 104)] {
+            statistics.update(temperature, timestamp);
+        }
+        assert_eq!(
+            statistics,
+            Statistics {
+                start: 100,
+                count: 4,
+                min: 19.0,
+                max: 26.0,
+                mean: 22.0,
+                min_timestamp: 102,
+                max_timestamp: 103,
+            }
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use super::Result;
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::nvs::{EspNvs, NvsDefault};
+
+/// Non-volatile store the settings and the slot table are kept in
+pub(crate) trait Store {
+    /// Reads the blob into the buffer, `None` if the key is not set
+    fn get_blob<'a>(&self, key: &str, buffer: &'a mut [u8]) -> Result<Option<&'a [u8]>>;
+
+    fn set_blob(&mut self, key: &str, bytes: &[u8]) -> Result<()>;
+}
+
+#[cfg(target_os = "espidf")]
+impl Store for EspNvs<NvsDefault> {
+    fn get_blob<'a>(&self, key: &str, buffer: &'a mut [u8]) -> Result<Option<&'a [u8]>> {
+        Ok(EspNvs::get_blob(self, key, buffer)?)
+    }
+
+    fn set_blob(&mut self, key: &str, bytes: &[u8]) -> Result<()> {
+        Ok(EspNvs::set_blob(self, key, bytes)?)
+    }
+}
+
+/// In-memory store, failing like the NVS on a buffer shorter than the blob
+#[cfg(test)]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Memory(std::collections::BTreeMap<String, Vec<u8>>);
+
+#[cfg(test)]
+impl Store for Memory {
+    fn get_blob<'a>(&self, key: &str, buffer: &'a mut [u8]) -> Result<Option<&'a [u8]>> {
+        let Some(bytes) = self.0.get(key) else {
+            return Ok(None);
+        };
+        let buffer = buffer.get_mut(..bytes.len()).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Blob {key} of {} bytes", bytes.len()),
+            )
+        })?;
+        buffer.copy_from_slice(bytes);
+        Ok(Some(buffer))
+    }
+
+    fn set_blob(&mut self, key: &str, bytes: &[u8]) -> Result<()> {
+        self.0.insert(key.to_owned(), bytes.to_vec());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_buffer() {
+        let mut memory = Memory::default();
+        memory.set_blob("key", &[1, 2, 3]).unwrap();
+        assert_eq!(
+            memory.get_blob("key", &mut [0; 4]).unwrap(),
+            Some(&[1, 2, 3][..])
+        );
+        assert!(memory.get_blob("key", &mut [0; 2]).is_err());
+        assert_eq!(memory.get_blob("other", &mut [0; 2]).unwrap(), None);
+    }
+}
@@ -0,0 +1,74 @@
+use super::{Error, Result, bus::Bus};
+use log::info;
+use std::thread;
+use tokio::sync::{mpsc, oneshot};
+
+const STACK_SIZE: usize = 8 * 1024;
+
+type Job<B> = Box<dyn FnOnce(&mut B) + Send>;
+
+/// Bus worker
+///
+/// Owns the bus on a dedicated thread, blocking 1-Wire transactions (a
+/// 12-bit conversion alone takes ~750 ms) never stall the async runtime.
+pub(crate) struct Worker<B> {
+    sender: mpsc::Sender<Job<B>>,
+}
+
+impl<B: Bus + Send + 'static> Worker<B> {
+    pub(crate) fn spawn(mut bus: B) -> Result<Self> {
+        let (sender, mut receiver) = mpsc::channel::<Job<B>>(9);
+        thread::Builder::new()
+            .name("temperature".to_owned())
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                info!("Temperature worker started");
+                while let Some(job) = receiver.blocking_recv() {
+                    job(&mut bus);
+                }
+                info!("Temperature worker stopped");
+            })?;
+        Ok(Self { sender })
+    }
+
+    /// Runs `f` on the worker thread and waits for its result
+    pub(crate) async fn call<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut B) -> T + Send + 'static,
+    ) -> Result<T> {
+        self.submit(f).await?.wait().await
+    }
+
+    /// Queues `f` on the worker thread without waiting for its result, jobs
+    /// submitted to several workers run in parallel
+    pub(crate) async fn submit<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut B) -> T + Send + 'static,
+    ) -> Result<Reply<T>> {
+        let (sender, receiver) = oneshot::channel();
+        self.sender
+            .send(Box::new(move |bus| {
+                let _ = sender.send(f(bus));
+            }))
+            .await
+            .map_err(|_| Error::Worker)?;
+        Ok(Reply(receiver))
+    }
+}
+
+/// Pending result of a submitted job
+pub(crate) struct Reply<T>(oneshot::Receiver<T>);
+
+impl<T> Reply<T> {
+    pub(crate) async fn wait(self) -> Result<T> {
+        self.0.await.map_err(|_| Error::Worker)
+    }
+}
+
+impl<B> Clone for Worker<B> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}